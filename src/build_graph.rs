@@ -0,0 +1,385 @@
+use super::{
+    docker_image::{BuildOptions, DockerImage, FingerprintError, NewDockerImageError},
+    dockerfile::Variables,
+    image_definition,
+    image_reference::ImageReference,
+    lockfile::Lockfile,
+};
+use failure::Fail;
+use std::{
+    collections::{HashMap, VecDeque},
+    io,
+    path::Path,
+};
+
+pub struct BuildGraph {
+    images: Vec<DockerImage>,
+    indices: HashMap<String, usize>,
+    dependencies: Vec<Vec<usize>>,
+}
+
+#[derive(Debug, Fail)]
+pub enum BuildGraphError {
+    #[fail(display = "Failed to list images")]
+    ListImagesError(#[cause] io::Error),
+
+    #[fail(display = "Failed to parse image description")]
+    NewDockerImageError(#[cause] NewDockerImageError),
+
+    #[fail(display = "Dependency cycle between images: {}", _0)]
+    DependencyCycle(String),
+
+    #[fail(display = "Failed to compute image fingerprint")]
+    FingerprintError(#[cause] FingerprintError),
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum VisitState {
+    Unvisited,
+    InProgress,
+    Done,
+}
+
+impl BuildGraph {
+    pub fn load_all(
+        images_dir: impl AsRef<Path>,
+        variables: Option<&Variables>,
+    ) -> Result<Self, BuildGraphError> {
+        let images_dir = images_dir.as_ref();
+        let image_tags = image_definition::list_image_tags(images_dir)
+            .map_err(BuildGraphError::ListImagesError)?;
+        let mut tag_namespaces: Vec<String> = image_tags
+            .iter()
+            .filter_map(|tag| tag.split('/').next())
+            .map(str::to_owned)
+            .collect();
+
+        tag_namespaces.dedup();
+
+        Self::load(images_dir, &tag_namespaces, image_tags, variables)
+    }
+
+    pub fn load(
+        images_dir: impl AsRef<Path>,
+        tag_namespaces: &[String],
+        image_tags: impl IntoIterator<Item = String>,
+        variables: Option<&Variables>,
+    ) -> Result<Self, BuildGraphError> {
+        let images_dir = images_dir.as_ref();
+        let tag_prefixes: Vec<String> = tag_namespaces
+            .iter()
+            .map(|namespace| format!("{}/", namespace))
+            .collect();
+        let is_local = |image_tag: &str| {
+            tag_prefixes
+                .iter()
+                .any(|tag_prefix| image_tag.starts_with(tag_prefix))
+        };
+        let mut tag_queue: VecDeque<String> = image_tags.into_iter().collect();
+        let mut graph = BuildGraph {
+            images: Vec::new(),
+            indices: HashMap::new(),
+            dependencies: Vec::new(),
+        };
+
+        while let Some(image_tag) = tag_queue.pop_front() {
+            if graph.indices.contains_key(&image_tag) {
+                continue;
+            }
+
+            let tag_namespace = tag_namespaces
+                .iter()
+                .zip(&tag_prefixes)
+                .find(|(_, tag_prefix)| image_tag.starts_with(tag_prefix.as_str()))
+                .or_else(|| tag_namespaces.iter().zip(&tag_prefixes).next())
+                .map(|(namespace, _)| namespace.as_str())
+                .unwrap_or("");
+
+            let docker_image = DockerImage::new(images_dir, &image_tag, tag_namespace, variables)
+                .map_err(BuildGraphError::NewDockerImageError)?;
+
+            if graph.indices.contains_key(docker_image.tag()) {
+                graph
+                    .indices
+                    .insert(image_tag, graph.indices[docker_image.tag()]);
+                continue;
+            }
+
+            tag_queue.extend(
+                docker_image
+                    .source_images()
+                    .filter(|source_tag| is_local(source_tag))
+                    .map(unversioned_tag),
+            );
+
+            graph.insert(image_tag, docker_image);
+        }
+
+        graph.link(is_local);
+
+        Ok(graph)
+    }
+
+    fn insert(&mut self, requested_tag: String, docker_image: DockerImage) {
+        let index = self.images.len();
+
+        self.indices.insert(docker_image.tag().to_owned(), index);
+        self.indices.insert(requested_tag, index);
+        self.images.push(docker_image);
+    }
+
+    fn link(&mut self, is_local: impl Fn(&str) -> bool) {
+        self.dependencies = self
+            .images
+            .iter()
+            .map(|docker_image| {
+                let mut dependencies: Vec<usize> = docker_image
+                    .source_images()
+                    .filter(|source_tag| is_local(source_tag))
+                    .filter_map(|source_tag| self.index(source_tag))
+                    .collect();
+
+                dependencies.sort();
+                dependencies.dedup();
+                dependencies
+            })
+            .collect();
+    }
+
+    pub fn pin_base_images(&mut self, lockfile: &Lockfile) {
+        for docker_image in &mut self.images {
+            docker_image.pin_base_images(lockfile);
+        }
+    }
+
+    pub fn external_images(&self) -> Vec<&str> {
+        let mut external_images: Vec<&str> = self
+            .images
+            .iter()
+            .flat_map(DockerImage::source_images)
+            .filter(|source_tag| self.index(source_tag).is_none())
+            .collect();
+
+        external_images.sort();
+        external_images.dedup();
+        external_images
+    }
+
+    pub fn len(&self) -> usize {
+        self.images.len()
+    }
+
+    pub fn index(&self, image_tag: &str) -> Option<usize> {
+        self.indices
+            .get(image_tag)
+            .or_else(|| self.indices.get(&unversioned_tag(image_tag)))
+            .cloned()
+    }
+
+    pub fn downstream(&self, index: usize) -> Vec<usize> {
+        let mut selected = vec![false; self.images.len()];
+        let mut queue = VecDeque::new();
+
+        selected[index] = true;
+        queue.push_back(index);
+
+        while let Some(current) = queue.pop_front() {
+            for (dependent, dependencies) in self.dependencies.iter().enumerate() {
+                if !selected[dependent] && dependencies.contains(&current) {
+                    selected[dependent] = true;
+                    queue.push_back(dependent);
+                }
+            }
+        }
+
+        (0..self.images.len())
+            .filter(|&index| selected[index])
+            .collect()
+    }
+
+    pub fn image(&self, index: usize) -> &DockerImage {
+        &self.images[index]
+    }
+
+    pub fn dependencies(&self, index: usize) -> &[usize] {
+        &self.dependencies[index]
+    }
+
+    pub fn fingerprints(
+        &self,
+        options: &BuildOptions,
+    ) -> Result<HashMap<String, String>, BuildGraphError> {
+        let mut fingerprints: HashMap<String, String> = HashMap::new();
+
+        for index in self.build_order()? {
+            let docker_image = &self.images[index];
+            let parent_fingerprints: Vec<&str> = self.dependencies[index]
+                .iter()
+                .map(|&dependency| fingerprints[self.images[dependency].tag()].as_str())
+                .collect();
+            let fingerprint = docker_image
+                .fingerprint(options, parent_fingerprints)
+                .map_err(BuildGraphError::FingerprintError)?;
+
+            fingerprints.insert(docker_image.tag().to_owned(), fingerprint);
+        }
+
+        Ok(fingerprints)
+    }
+
+    pub fn build_order(&self) -> Result<Vec<usize>, BuildGraphError> {
+        let mut states = vec![VisitState::Unvisited; self.images.len()];
+        let mut path = Vec::new();
+        let mut order = Vec::with_capacity(self.images.len());
+
+        for index in 0..self.images.len() {
+            self.visit(index, &mut states, &mut path, &mut order)?;
+        }
+
+        Ok(order)
+    }
+
+    fn visit(
+        &self,
+        index: usize,
+        states: &mut [VisitState],
+        path: &mut Vec<usize>,
+        order: &mut Vec<usize>,
+    ) -> Result<(), BuildGraphError> {
+        match states[index] {
+            VisitState::Done => return Ok(()),
+            VisitState::InProgress => {
+                let cycle_start = path
+                    .iter()
+                    .position(|&visited| visited == index)
+                    .unwrap_or(0);
+                let mut cycle: Vec<&str> = path[cycle_start..]
+                    .iter()
+                    .map(|&visited| self.images[visited].tag())
+                    .collect();
+
+                cycle.push(self.images[index].tag());
+
+                return Err(BuildGraphError::DependencyCycle(cycle.join(" -> ")));
+            }
+            VisitState::Unvisited => {}
+        }
+
+        states[index] = VisitState::InProgress;
+        path.push(index);
+
+        for &dependency in &self.dependencies[index] {
+            self.visit(dependency, states, path, order)?;
+        }
+
+        path.pop();
+        states[index] = VisitState::Done;
+        order.push(index);
+
+        Ok(())
+    }
+}
+
+fn unversioned_tag(image_tag: &str) -> String {
+    match image_tag.parse::<ImageReference>() {
+        Ok(reference) => reference.without_version().to_string(),
+        Err(_) => image_tag.to_owned(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{super::test_support::write_image, *};
+    use tempfile::TempDir;
+
+    fn load(images_dir: &TempDir, image_tag: &str) -> BuildGraph {
+        BuildGraph::load(
+            images_dir.path(),
+            &["ns".to_owned()],
+            vec![image_tag.to_owned()],
+            Some(&Variables::new()),
+        )
+        .unwrap()
+    }
+
+    fn write_diamond(images_dir: &TempDir) {
+        write_image(images_dir, "ns/base", "from: alpine\n");
+        write_image(images_dir, "ns/left", "from: ns/base\n");
+        write_image(images_dir, "ns/right", "from: ns/base\n");
+        write_image(
+            images_dir,
+            "ns/top",
+            "from: ns/left\n---\nfrom: ns/right\ncopy:\n  - from: /app\n    to: /app\n    stage: 0\n",
+        );
+    }
+
+    #[test]
+    fn shared_dependency_is_loaded_once() {
+        let images_dir = TempDir::new().unwrap();
+
+        write_diamond(&images_dir);
+
+        let build_graph = load(&images_dir, "ns/top");
+        let mut image_tags: Vec<&str> = (0..build_graph.len())
+            .map(|index| build_graph.image(index).tag())
+            .collect();
+
+        image_tags.sort();
+        assert_eq!(image_tags, vec!["ns/base", "ns/left", "ns/right", "ns/top"]);
+        assert_eq!(build_graph.external_images(), vec!["alpine"]);
+    }
+
+    #[test]
+    fn build_order_puts_dependencies_first() {
+        let images_dir = TempDir::new().unwrap();
+
+        write_diamond(&images_dir);
+
+        let build_graph = load(&images_dir, "ns/top");
+        let order: Vec<&str> = build_graph
+            .build_order()
+            .unwrap()
+            .into_iter()
+            .map(|index| build_graph.image(index).tag())
+            .collect();
+        let position = |image_tag| order.iter().position(|&tag| tag == image_tag).unwrap();
+
+        assert_eq!(order.len(), 4);
+        assert!(position("ns/base") < position("ns/left"));
+        assert!(position("ns/base") < position("ns/right"));
+        assert!(position("ns/left") < position("ns/top"));
+        assert!(position("ns/right") < position("ns/top"));
+    }
+
+    #[test]
+    fn build_order_reports_cycles() {
+        let images_dir = TempDir::new().unwrap();
+
+        write_image(&images_dir, "ns/c1", "from: ns/c2\n");
+        write_image(&images_dir, "ns/c2", "from: ns/c1\n");
+
+        match load(&images_dir, "ns/c1").build_order() {
+            Err(BuildGraphError::DependencyCycle(cycle)) => {
+                assert_eq!(cycle, "ns/c1 -> ns/c2 -> ns/c1");
+            }
+            _ => panic!("expected DependencyCycle"),
+        }
+    }
+
+    #[test]
+    fn loading_without_variables_keeps_conditional_stages() {
+        let images_dir = TempDir::new().unwrap();
+
+        write_image(
+            &images_dir,
+            "ns/app",
+            "from: golang\nwhen: compile\nrun: go build\n---\nfrom: alpine\n",
+        );
+
+        let conditional = BuildGraph::load_all(images_dir.path(), Some(&Variables::new())).unwrap();
+        let unconditional = BuildGraph::load_all(images_dir.path(), None).unwrap();
+
+        assert_eq!(conditional.external_images(), vec!["alpine"]);
+        assert_eq!(unconditional.external_images(), vec!["alpine", "golang"]);
+    }
+}
@@ -0,0 +1,110 @@
+pub struct Instruction {
+    line: usize,
+    failed_line: usize,
+    text: String,
+}
+
+impl Instruction {
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    pub fn failed_line(&self) -> usize {
+        self.failed_line
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+}
+
+pub fn failed_instruction(log: &str, dockerfile: &str) -> Option<Instruction> {
+    let instructions = instructions(dockerfile);
+
+    if let Some(line) = reported_line(log) {
+        let mut instruction = instructions
+            .into_iter()
+            .rev()
+            .find(|instruction| instruction.line <= line)?;
+
+        instruction.failed_line = line;
+
+        return Some(instruction);
+    }
+
+    let reported_instruction = reported_instruction(log)?;
+
+    instructions.into_iter().find(|instruction| {
+        let text = normalize(&instruction.text);
+
+        text == reported_instruction || text.starts_with(&reported_instruction)
+    })
+}
+
+fn reported_line(log: &str) -> Option<usize> {
+    log.lines()
+        .rev()
+        .find_map(|line| line.trim().strip_prefix("Dockerfile:")?.parse().ok())
+}
+
+fn reported_instruction(log: &str) -> Option<String> {
+    let mut failed_step = None;
+
+    for line in log.lines() {
+        let line = line.trim();
+
+        if let Some(step) = line.strip_prefix("> [") {
+            return instruction_after_step(step).map(|text| normalize(text.trim_end_matches(':')));
+        } else if let Some(step) = line.strip_prefix("ERROR [") {
+            return instruction_after_step(step).map(normalize);
+        } else if line.starts_with("Step ") {
+            failed_step = line
+                .find(" : ")
+                .map(|position| normalize(&line[(position + 3)..]));
+        }
+    }
+
+    failed_step
+}
+
+fn instruction_after_step(step: &str) -> Option<&str> {
+    step.find("] ").map(|position| &step[(position + 2)..])
+}
+
+fn instructions(dockerfile: &str) -> Vec<Instruction> {
+    let mut instructions = Vec::new();
+    let mut current: Option<Instruction> = None;
+
+    for (index, line) in dockerfile.lines().enumerate() {
+        let trimmed = line.trim();
+
+        let instruction = match current.as_mut() {
+            Some(instruction) => instruction,
+            None if trimmed.is_empty() || trimmed.starts_with('#') => continue,
+            None => current.get_or_insert(Instruction {
+                line: index + 1,
+                failed_line: index + 1,
+                text: String::new(),
+            }),
+        };
+
+        if !instruction.text.is_empty() {
+            instruction.text.push(' ');
+        }
+
+        match trimmed.strip_suffix('\\') {
+            Some(continued) => instruction.text.push_str(continued.trim_end()),
+            None => {
+                instruction.text.push_str(trimmed);
+                instructions.extend(current.take());
+            }
+        }
+    }
+
+    instructions.extend(current);
+    instructions
+}
+
+fn normalize(instruction: &str) -> String {
+    instruction.split_whitespace().collect::<Vec<_>>().join(" ")
+}
@@ -0,0 +1,127 @@
+use super::{
+    build_graph::{BuildGraph, BuildGraphError},
+    docker_image::DockerImage,
+};
+use std::{collections::BTreeSet, sync::mpsc, thread};
+
+pub struct BuildScheduler<'a> {
+    graph: &'a BuildGraph,
+    order: Vec<usize>,
+    jobs: usize,
+    keep_going: bool,
+}
+
+impl<'a> BuildScheduler<'a> {
+    pub fn new(graph: &'a BuildGraph, jobs: usize) -> Result<Self, BuildGraphError> {
+        Ok(BuildScheduler {
+            graph,
+            order: graph.build_order()?,
+            jobs: jobs.max(1),
+            keep_going: false,
+        })
+    }
+
+    pub fn restrict_to(&mut self, images: &[usize]) {
+        self.order.retain(|index| images.contains(index));
+    }
+
+    pub fn set_keep_going(&mut self, keep_going: bool) {
+        self.keep_going = keep_going;
+    }
+
+    pub fn images(&self) -> impl Iterator<Item = &DockerImage> {
+        self.order.iter().map(move |&index| self.graph.image(index))
+    }
+
+    pub fn run<E>(&self, build: impl Fn(&DockerImage) -> Result<(), E> + Sync) -> Result<(), E>
+    where
+        E: Send,
+    {
+        let node_count = self.graph.len();
+        let mut scheduled = vec![false; node_count];
+        let mut positions = vec![0; node_count];
+        let mut remaining_dependencies = vec![0; node_count];
+        let mut dependents = vec![Vec::new(); node_count];
+        let mut ready = BTreeSet::new();
+
+        for &index in &self.order {
+            scheduled[index] = true;
+        }
+
+        for (position, &index) in self.order.iter().enumerate() {
+            let dependencies: Vec<usize> = self
+                .graph
+                .dependencies(index)
+                .iter()
+                .cloned()
+                .filter(|&dependency| scheduled[dependency])
+                .collect();
+
+            positions[index] = position;
+            remaining_dependencies[index] = dependencies.len();
+
+            for dependency in dependencies {
+                dependents[dependency].push(index);
+            }
+
+            if remaining_dependencies[index] == 0 {
+                ready.insert(position);
+            }
+        }
+
+        let (sender, receiver) = mpsc::channel();
+        let build = &build;
+
+        thread::scope(|scope| {
+            let mut running = 0;
+            let mut failure = None;
+
+            loop {
+                while (failure.is_none() || self.keep_going) && running < self.jobs {
+                    let index = match ready.pop_first() {
+                        Some(position) => self.order[position],
+                        None => break,
+                    };
+                    let sender = sender.clone();
+                    let docker_image = self.graph.image(index);
+
+                    scope.spawn(move || sender.send((index, build(docker_image))));
+                    running += 1;
+                }
+
+                if running == 0 {
+                    break;
+                }
+
+                let (index, result) = match receiver.recv() {
+                    Ok(message) => message,
+                    Err(_) => break,
+                };
+
+                running -= 1;
+
+                match result {
+                    Ok(()) => {
+                        for &dependent in &dependents[index] {
+                            remaining_dependencies[dependent] -= 1;
+
+                            if remaining_dependencies[dependent] == 0 {
+                                ready.insert(positions[dependent]);
+                            }
+                        }
+                    }
+                    Err(error) => {
+                        if failure.is_none() {
+                            failure = Some(error);
+                        }
+                    }
+                }
+            }
+
+            match failure {
+                Some(error) => Err(error),
+                None => Ok(()),
+            }
+        })
+    }
+}
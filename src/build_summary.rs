@@ -0,0 +1,291 @@
+use super::docker_image::DockerImage;
+use failure::Fail;
+use serde::Serialize;
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{self, BufWriter},
+    path::Path,
+    sync::Mutex,
+    time::{Duration, Instant, SystemTime},
+};
+
+#[derive(Clone, Copy, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum BuildStatus {
+    Built,
+    #[serde(rename = "up-to-date")]
+    Skipped,
+    Failed,
+    Blocked,
+    NotBuilt,
+}
+
+pub struct BuildSummary {
+    results: Mutex<HashMap<String, BuildResult>>,
+}
+
+struct BuildResult {
+    status: BuildStatus,
+    started_at: Option<SystemTime>,
+    duration: Option<Duration>,
+    error: Option<String>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "kebab-case")]
+struct ReportEntry {
+    tag: String,
+    dockerfile_hash: String,
+    started_at: Option<String>,
+    finished_at: Option<String>,
+    outcome: BuildStatus,
+    image_id: Option<String>,
+    #[serde(skip)]
+    size: Option<u64>,
+    #[serde(skip)]
+    duration: Option<Duration>,
+    error: Option<String>,
+}
+
+#[derive(Serialize)]
+struct Report {
+    images: Vec<ReportEntry>,
+}
+
+impl BuildSummary {
+    pub fn new() -> Self {
+        BuildSummary {
+            results: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn record<T, E>(
+        &self,
+        docker_image: &DockerImage,
+        build: impl FnOnce() -> Result<(BuildStatus, T), E>,
+    ) -> Result<T, E>
+    where
+        E: Fail,
+    {
+        let started_at = SystemTime::now();
+        let start = Instant::now();
+        let result = build();
+        let (status, error) = match &result {
+            Ok((status, _)) => (*status, None),
+            Err(error) => (BuildStatus::Failed, Some(error_message(error))),
+        };
+
+        self.results
+            .lock()
+            .expect("Build summary lock was poisoned")
+            .insert(
+                docker_image.tag().to_owned(),
+                BuildResult {
+                    status,
+                    started_at: Some(started_at),
+                    duration: Some(start.elapsed()),
+                    error,
+                },
+            );
+
+        result.map(|(_, value)| value)
+    }
+
+    pub fn mark_blocked<'a>(&self, docker_images: impl IntoIterator<Item = &'a DockerImage>) {
+        let mut results = self
+            .results
+            .lock()
+            .expect("Build summary lock was poisoned");
+
+        for docker_image in docker_images {
+            results
+                .entry(docker_image.tag().to_owned())
+                .or_insert_with(|| {
+                    println!(
+                        "Skipping image with a failed dependency: {}",
+                        docker_image.tag()
+                    );
+
+                    BuildResult {
+                        status: BuildStatus::Blocked,
+                        started_at: None,
+                        duration: None,
+                        error: None,
+                    }
+                });
+        }
+    }
+
+    pub fn finish<'a>(
+        &self,
+        docker_images: impl IntoIterator<Item = &'a DockerImage>,
+        report_path: Option<&Path>,
+    ) -> Result<(), io::Error> {
+        let entries = self.entries(docker_images);
+
+        print_table(&entries);
+
+        match report_path {
+            Some(report_path) => write_report(report_path, entries),
+            None => Ok(()),
+        }
+    }
+
+    pub fn write_report<'a>(
+        &self,
+        docker_images: impl IntoIterator<Item = &'a DockerImage>,
+        report_path: &Path,
+    ) -> Result<(), io::Error> {
+        write_report(report_path, self.entries(docker_images))
+    }
+
+    fn entries<'a>(
+        &self,
+        docker_images: impl IntoIterator<Item = &'a DockerImage>,
+    ) -> Vec<ReportEntry> {
+        let results = self
+            .results
+            .lock()
+            .expect("Build summary lock was poisoned");
+
+        docker_images
+            .into_iter()
+            .map(|docker_image| {
+                let result = results.get(docker_image.tag());
+                let status = result
+                    .map(|result| result.status)
+                    .unwrap_or(BuildStatus::NotBuilt);
+                let id_and_size = match status {
+                    BuildStatus::Built | BuildStatus::Skipped => docker_image.id_and_size().ok(),
+                    BuildStatus::Failed | BuildStatus::Blocked | BuildStatus::NotBuilt => None,
+                };
+
+                ReportEntry {
+                    tag: docker_image.tag().to_owned(),
+                    dockerfile_hash: docker_image.dockerfile_hash(),
+                    started_at: result.and_then(|result| result.started_at).map(format_time),
+                    finished_at: result
+                        .and_then(|result| Some(result.started_at? + result.duration?))
+                        .map(format_time),
+                    outcome: status,
+                    image_id: id_and_size.as_ref().map(|(id, _)| id.clone()),
+                    size: id_and_size.map(|(_, size)| size),
+                    duration: result.and_then(|result| result.duration),
+                    error: result.and_then(|result| result.error.clone()),
+                }
+            })
+            .collect()
+    }
+}
+
+fn print_table(entries: &[ReportEntry]) {
+    let mut rows = vec![[
+        "IMAGE".to_owned(),
+        "STATUS".to_owned(),
+        "DURATION".to_owned(),
+        "IMAGE ID".to_owned(),
+        "SIZE".to_owned(),
+    ]];
+
+    for entry in entries {
+        let status = match entry.outcome {
+            BuildStatus::Built => "built",
+            BuildStatus::Skipped => "up to date",
+            BuildStatus::Failed => "failed",
+            BuildStatus::Blocked => "skipped (failed dependency)",
+            BuildStatus::NotBuilt => "not built",
+        };
+
+        rows.push([
+            entry.tag.clone(),
+            status.to_owned(),
+            entry
+                .duration
+                .map(format_duration)
+                .unwrap_or_else(|| "-".to_owned()),
+            entry
+                .image_id
+                .as_deref()
+                .map(|id| short_id(id).to_owned())
+                .unwrap_or_else(|| "-".to_owned()),
+            entry
+                .size
+                .map(format_size)
+                .unwrap_or_else(|| "-".to_owned()),
+        ]);
+    }
+
+    let mut widths = [0; 5];
+
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.len());
+        }
+    }
+
+    println!();
+
+    for row in &rows {
+        let line: Vec<String> = row
+            .iter()
+            .zip(&widths)
+            .map(|(cell, &width)| format!("{:width$}", cell, width = width))
+            .collect();
+
+        println!("{}", line.join("  ").trim_end());
+    }
+}
+
+fn write_report(report_path: &Path, images: Vec<ReportEntry>) -> Result<(), io::Error> {
+    let writer = BufWriter::new(File::create(report_path)?);
+
+    serde_json::to_writer_pretty(writer, &Report { images })?;
+
+    Ok(())
+}
+
+fn error_message(error: &dyn Fail) -> String {
+    let mut messages = vec![error.to_string()];
+
+    messages.extend(error.iter_causes().map(ToString::to_string));
+    messages.join(": ")
+}
+
+fn format_time(time: SystemTime) -> String {
+    humantime::format_rfc3339_seconds(time).to_string()
+}
+
+fn format_duration(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+
+    if seconds >= 60 {
+        format!("{}m{:02}s", seconds / 60, seconds % 60)
+    } else {
+        format!("{}.{}s", seconds, duration.subsec_millis() / 100)
+    }
+}
+
+fn short_id(id: &str) -> &str {
+    let id = id.trim_start_matches("sha256:");
+
+    &id[..id.len().min(12)]
+}
+
+fn format_size(size: u64) -> String {
+    const UNITS: &[&str] = &["B", "kB", "MB", "GB", "TB"];
+
+    let mut value = size as f64;
+    let mut unit = 0;
+
+    while value >= 1000.0 && unit + 1 < UNITS.len() {
+        value /= 1000.0;
+        unit += 1;
+    }
+
+    if unit == 0 {
+        format!("{}{}", size, UNITS[unit])
+    } else {
+        format!("{:.1}{}", value, UNITS[unit])
+    }
+}
@@ -1,13 +1,30 @@
-use super::super::{
-    config::Config,
-    docker_image::DockerImage,
-    docker_image::{BuildDockerImageError, NewDockerImageError},
+use super::{
+    super::{
+        build_graph::{BuildGraph, BuildGraphError},
+        build_scheduler::BuildScheduler,
+        build_summary::{BuildStatus, BuildSummary},
+        config::Config,
+        docker::shell_quote,
+        docker_image::{
+            BuildDockerImageError, BuildOptions, DockerImage, PushDockerImageError,
+            TestDockerImageError,
+        },
+        dockerfile::Variables,
+        image_definition,
+        lockfile::{Lockfile, LockfileError},
+        APP_INFO,
+    },
+    images_dir, parse_key_value, tag_namespace,
 };
+use app_dirs::{AppDataType, AppDirsError};
 use failure::Fail;
 use std::{
-    collections::VecDeque,
     fmt::{self, Display, Formatter},
-    path::PathBuf,
+    fs, io,
+    path::{Path, PathBuf},
+    process,
+    sync::Mutex,
+    time::{SystemTime, UNIX_EPOCH},
 };
 use structopt::StructOpt;
 
@@ -20,7 +37,105 @@ pub struct Build {
         parse(from_os_str)
     )]
     images_dir: Option<PathBuf>,
-    image_tag: String,
+
+    /// Number of images to build in parallel
+    #[structopt(short = "j", long = "jobs", default_value = "1")]
+    jobs: usize,
+
+    /// Rebuild images even if they are up to date
+    #[structopt(long = "force")]
+    force: bool,
+
+    /// Target platform of the built images
+    #[structopt(long = "platform")]
+    platform: Option<String>,
+
+    /// Build argument passed to docker and available to conditions, as NAME=VALUE
+    #[structopt(
+        long = "build-arg",
+        raw(number_of_values = "1"),
+        parse(try_from_str = "parse_key_value")
+    )]
+    build_args: Vec<(String, String)>,
+
+    /// Variable available to conditions, as NAME=VALUE
+    #[structopt(
+        long = "var",
+        raw(number_of_values = "1"),
+        parse(try_from_str = "parse_key_value")
+    )]
+    variables: Vec<(String, String)>,
+
+    /// Build without using the docker build cache
+    #[structopt(long = "no-cache")]
+    no_cache: bool,
+
+    /// Always pull newer versions of the base images
+    #[structopt(long = "pull")]
+    pull: bool,
+
+    /// Networking mode for the RUN instructions
+    #[structopt(long = "network")]
+    network: Option<String>,
+
+    /// Stage to build in every image
+    #[structopt(long = "target")]
+    target: Option<String>,
+
+    /// Type of progress output passed to docker
+    #[structopt(long = "progress")]
+    progress: Option<String>,
+
+    /// Label added to the built images, as NAME=VALUE
+    #[structopt(
+        long = "label",
+        raw(number_of_values = "1"),
+        parse(try_from_str = "parse_key_value")
+    )]
+    labels: Vec<(String, String)>,
+
+    /// Rebuild an image and every image that depends on it
+    #[structopt(long = "downstream")]
+    downstream: Option<String>,
+
+    /// Print the build plan without building any image
+    #[structopt(long = "dry-run")]
+    dry_run: bool,
+
+    /// Keep building the images whose dependencies were built after a failure
+    #[structopt(long = "keep-going")]
+    keep_going: bool,
+
+    /// Open a shell in the image built up to the failed instruction of each failed build,
+    /// once all the other images have finished building
+    #[structopt(long = "debug-on-failure")]
+    debug_on_failure: bool,
+
+    /// Directory to keep the generated Dockerfiles in, instead of the one in the config file
+    #[structopt(long = "keep-dockerfiles", parse(from_os_str))]
+    keep_dockerfiles: Option<PathBuf>,
+
+    /// Write a JSON report of the build to this file
+    #[structopt(long = "report", parse(from_os_str))]
+    report: Option<PathBuf>,
+
+    /// Push the images to the registry after building them
+    #[structopt(long = "push")]
+    push: bool,
+
+    /// Registry to push the images to, instead of the one in the config file
+    #[structopt(long = "registry")]
+    registry: Option<String>,
+
+    /// Build every image in the images directory
+    #[structopt(long = "all")]
+    all: bool,
+
+    /// Namespace or glob pattern of the images to build with --all
+    #[structopt(long = "filter", requires = "all")]
+    filter: Option<String>,
+
+    image_tag: Option<String>,
 }
 
 #[derive(Debug, Fail)]
@@ -31,75 +146,566 @@ pub enum RunBuildError {
     )]
     NoTagNamespace(String),
 
-    #[fail(display = "Failed to parse image description")]
-    NewDockerImageError(#[cause] NewDockerImageError),
+    #[fail(display = "Missing image tag to build")]
+    MissingImageTag,
+
+    #[fail(display = "Only one of an image tag, --downstream or --all can be used")]
+    ConflictingImageTags,
+
+    #[fail(display = "Invalid image filter pattern: {}", _0)]
+    InvalidFilter(String, #[cause] glob::PatternError),
+
+    #[fail(display = "Failed to list images in images directory")]
+    ListImagesError(#[cause] io::Error),
+
+    #[fail(display = "Image not found in images directory: {}", _0)]
+    UnknownImage(String),
+
+    #[fail(display = "Failed to resolve image dependencies")]
+    BuildGraphError(#[cause] BuildGraphError),
+
+    #[fail(display = "Failed to create build log directory")]
+    LogDirError(#[cause] AppDirsError),
+
+    #[fail(display = "Failed to create directory to keep Dockerfiles in: {}", _0)]
+    KeepDockerfilesDirError(String, #[cause] io::Error),
+
+    #[fail(display = "Failed to load pinned base images")]
+    LockfileError(#[cause] LockfileError),
+
+    #[fail(display = "Failed to write build report: {}", _0)]
+    WriteReportError(String, #[cause] io::Error),
 
     #[fail(display = "Failed to build docker image")]
     BuildImageError(#[cause] BuildDockerImageError),
+
+    #[fail(display = "Docker image failed its tests")]
+    TestImageError(#[cause] TestDockerImageError),
+
+    #[fail(display = "Failed to tag tested docker image: {}", _0)]
+    TagTestedImageError(String, #[cause] io::Error),
+
+    #[fail(display = "Failed to push docker image")]
+    PushImageError(#[cause] PushDockerImageError),
+}
+
+struct ImageSelection {
+    tag_namespaces: Vec<String>,
+    image_tags: Vec<String>,
+    downstream_tag: Option<String>,
 }
 
 impl Build {
     pub fn run(self, config: Config) -> Result<(), RunBuildError> {
-        let mut tag_queue = VecDeque::new();
-        let mut build_queue = Vec::new();
-        let images_dir = Self::images_dir(self.images_dir, &config);
-
-        let tag_prefix = match config.tag_namespace {
-            Some(namespace) => format!("{}/", namespace),
-            None => {
-                if let Some(end) = self.image_tag.find("/") {
-                    self.image_tag[0..(end + 1)].to_owned()
+        let images_dir = images_dir(self.images_dir.clone(), &config);
+
+        let variables = self.condition_variables();
+
+        let ImageSelection {
+            tag_namespaces,
+            image_tags,
+            downstream_tag,
+        } = self.select_images(&images_dir, &config)?;
+
+        let lockfile = Lockfile::load(&images_dir).map_err(RunBuildError::LockfileError)?;
+        let mut build_graph =
+            BuildGraph::load(&images_dir, &tag_namespaces, image_tags, Some(&variables))
+                .map_err(RunBuildError::BuildGraphError)?;
+
+        build_graph.pin_base_images(&lockfile);
+
+        let mut build_scheduler =
+            BuildScheduler::new(&build_graph, self.jobs).map_err(RunBuildError::BuildGraphError)?;
+
+        build_scheduler.set_keep_going(self.keep_going);
+
+        if let Some(image_tag) = &downstream_tag {
+            let index = build_graph
+                .index(image_tag)
+                .ok_or_else(|| RunBuildError::UnknownImage(image_tag.clone()))?;
+
+            build_scheduler.restrict_to(&build_graph.downstream(index));
+        }
+
+        let keep_dockerfiles = self
+            .keep_dockerfiles
+            .or_else(|| config.keep_dockerfiles.as_ref().map(PathBuf::from));
+        let log_dir = if self.dry_run {
+            None
+        } else {
+            if let Some(keep_dockerfiles) = &keep_dockerfiles {
+                fs::create_dir_all(keep_dockerfiles).map_err(|error| {
+                    RunBuildError::KeepDockerfilesDirError(
+                        keep_dockerfiles.display().to_string(),
+                        error,
+                    )
+                })?;
+            }
+
+            Some(build_log_dir()?)
+        };
+        let build_options = BuildOptions {
+            platform: self.platform,
+            build_args: self.build_args,
+            prefix_output: self.jobs > 1,
+            no_cache: self.no_cache,
+            pull: self.pull,
+            network: self.network,
+            target: self.target,
+            progress: self.progress,
+            labels: self.labels,
+            log_dir,
+            keep_dockerfiles,
+        };
+
+        let fingerprints = build_graph
+            .fingerprints(&build_options)
+            .map_err(RunBuildError::BuildGraphError)?;
+
+        let force = self.force || downstream_tag.is_some();
+        let push = self.push;
+        let registry = self.registry.or(config.registry);
+        let registry = registry.as_deref();
+        let should_skip = |docker_image: &DockerImage, fingerprint: &str| {
+            let image_options = docker_image.build_options(&build_options);
+
+            !force
+                && !image_options.no_cache
+                && !image_options.pull
+                && docker_image.is_up_to_date(fingerprint)
+        };
+
+        if self.dry_run {
+            for (position, docker_image) in build_scheduler.images().enumerate() {
+                let fingerprint = fingerprints[docker_image.tag()].as_str();
+
+                if should_skip(docker_image, fingerprint) {
+                    println!(
+                        "{}. {} (up to date, skipped)",
+                        position + 1,
+                        docker_image.tag()
+                    );
                 } else {
-                    return Err(RunBuildError::NoTagNamespace(self.image_tag));
+                    println!("{}. {}", position + 1, docker_image.tag());
+                    println!(
+                        "   {}",
+                        docker_image.build_command_line(&build_options, Some(fingerprint))
+                    );
+                }
+
+                if push {
+                    for command_line in docker_image.push_command_lines(registry) {
+                        println!("   {}", command_line);
+                    }
                 }
             }
-        };
-        let tag_namespace = &tag_prefix[0..(tag_prefix.len() - 1)];
 
-        let docker_image = DockerImage::new(&images_dir, &self.image_tag, tag_namespace)
-            .map_err(RunBuildError::NewDockerImageError)?;
+            return match &self.report {
+                Some(report_path) => BuildSummary::new()
+                    .write_report(build_scheduler.images(), report_path)
+                    .map_err(|error| {
+                        RunBuildError::WriteReportError(report_path.display().to_string(), error)
+                    }),
+                None => Ok(()),
+            };
+        }
+
+        let build_summary = BuildSummary::new();
+        let failed_builds = Mutex::new(Vec::new());
+        let result = build_scheduler.run(|docker_image| {
+            build_summary.record(docker_image, || {
+                let fingerprint = fingerprints[docker_image.tag()].as_str();
+                let status = if should_skip(docker_image, fingerprint) {
+                    println!("Skipping up to date image: {}", docker_image.tag());
+                    BuildStatus::Skipped
+                } else {
+                    let test_tag = docker_image.test_tag(fingerprint);
+                    let built = match &test_tag {
+                        Some(test_tag) => {
+                            docker_image.build_as(&build_options, Some(fingerprint), test_tag)
+                        }
+                        None => docker_image.build(&build_options, Some(fingerprint)),
+                    };
+
+                    if let Err(error) = built {
+                        failed_builds
+                            .lock()
+                            .expect("Failed builds lock was poisoned")
+                            .push(docker_image.tag().to_owned());
+
+                        return Err(RunBuildError::BuildImageError(error));
+                    }
+
+                    if let Some(test_tag) = &test_tag {
+                        if let Err(error) = docker_image.test_image(test_tag) {
+                            if let Err(error) = docker_image.remove_test_tag(test_tag) {
+                                eprintln!(
+                                    "Failed to remove image that failed its tests {}: {}",
+                                    test_tag, error
+                                );
+                            }
+
+                            return Err(RunBuildError::TestImageError(error));
+                        }
+
+                        docker_image.tag_tested(test_tag).map_err(|error| {
+                            RunBuildError::TagTestedImageError(docker_image.tag().to_owned(), error)
+                        })?;
+                    }
 
-        tag_queue.extend(docker_image.source_images().map(|tag| tag.to_owned()));
-        build_queue.push(docker_image);
+                    BuildStatus::Built
+                };
 
-        while let Some(source_image_tag) = tag_queue.pop_front() {
-            if source_image_tag.starts_with(&tag_prefix) {
-                let docker_image = DockerImage::new(&images_dir, &source_image_tag, tag_namespace)
-                    .map_err(RunBuildError::NewDockerImageError)?;
+                if push {
+                    docker_image
+                        .push(registry)
+                        .map_err(RunBuildError::PushImageError)?;
+                }
+
+                Ok((status, ()))
+            })
+        });
+
+        if self.keep_going {
+            build_summary.mark_blocked(build_scheduler.images());
+        }
+
+        let report_path = self.report;
+        let report = build_summary.finish(build_scheduler.images(), report_path.as_deref());
 
-                tag_queue.extend(docker_image.source_images().map(|tag| tag.to_owned()));
-                build_queue.push(docker_image);
+        if self.debug_on_failure {
+            let failed_builds = failed_builds
+                .into_inner()
+                .expect("Failed builds lock was poisoned");
+            let failed_images = build_scheduler
+                .images()
+                .filter(|docker_image| failed_builds.iter().any(|tag| tag == docker_image.tag()));
+
+            for docker_image in failed_images {
+                if let Err(error) = docker_image.debug_failed_build(&build_options) {
+                    eprintln!(
+                        "Failed to debug build of image {}: {}",
+                        docker_image.tag(),
+                        error
+                    );
+
+                    let error: &dyn Fail = &error;
+
+                    for cause in error.iter_causes() {
+                        eprintln!("       {}", cause);
+                    }
+                }
             }
         }
 
-        for docker_image in build_queue.into_iter().rev() {
-            docker_image
-                .build()
-                .map_err(RunBuildError::BuildImageError)?;
+        let report = report.map_err(|error| {
+            RunBuildError::WriteReportError(
+                report_path.unwrap_or_default().display().to_string(),
+                error,
+            )
+        });
+
+        if let (Err(_), Err(error)) = (&result, &report) {
+            eprintln!("{}", error);
+
+            let error: &dyn Fail = error;
+
+            for cause in error.iter_causes() {
+                eprintln!("       {}", cause);
+            }
         }
 
-        Ok(())
+        result?;
+        report
+    }
+
+    fn condition_variables(&self) -> Variables {
+        self.build_args
+            .iter()
+            .chain(&self.variables)
+            .cloned()
+            .collect()
     }
 
-    fn images_dir(images_dir: Option<PathBuf>, config: &Config) -> PathBuf {
-        match images_dir {
-            Some(path) => path,
-            None => PathBuf::from(match &config.images_dir {
-                Some(path) => path.as_str(),
-                None => "/project",
-            }),
+    fn select_images(
+        &self,
+        images_dir: &Path,
+        config: &Config,
+    ) -> Result<ImageSelection, RunBuildError> {
+        if self.all {
+            if self.image_tag.is_some() || self.downstream.is_some() {
+                return Err(RunBuildError::ConflictingImageTags);
+            }
+
+            let pattern = match &self.filter {
+                Some(filter) => {
+                    let pattern = if filter.contains('/') {
+                        filter.clone()
+                    } else {
+                        format!("{}/*", filter)
+                    };
+
+                    Some(
+                        glob::Pattern::new(&pattern)
+                            .map_err(|error| RunBuildError::InvalidFilter(pattern, error))?,
+                    )
+                }
+                None => None,
+            };
+            let image_tags: Vec<String> = image_definition::list_image_tags(images_dir)
+                .map_err(RunBuildError::ListImagesError)?
+                .into_iter()
+                .filter(|tag| match &pattern {
+                    Some(pattern) => pattern.matches(tag),
+                    None => true,
+                })
+                .collect();
+            let mut tag_namespaces: Vec<String> = image_tags
+                .iter()
+                .filter_map(|tag| tag.split('/').next())
+                .map(str::to_owned)
+                .collect();
+
+            tag_namespaces.dedup();
+
+            return Ok(ImageSelection {
+                tag_namespaces,
+                image_tags,
+                downstream_tag: None,
+            });
+        }
+
+        let (image_tag, downstream) = match (&self.image_tag, &self.downstream) {
+            (Some(image_tag), None) => (image_tag, false),
+            (None, Some(image_tag)) => (image_tag, true),
+            (None, None) => return Err(RunBuildError::MissingImageTag),
+            (Some(_), Some(_)) => return Err(RunBuildError::ConflictingImageTags),
+        };
+
+        let tag_namespace = match tag_namespace(image_tag, config) {
+            Some(namespace) => namespace,
+            None => return Err(RunBuildError::NoTagNamespace(image_tag.clone())),
+        };
+        let image_tag = if image_tag.contains('/') {
+            image_tag.clone()
+        } else {
+            format!("{}/{}", tag_namespace, image_tag)
+        };
+
+        if downstream {
+            let tag_prefix = format!("{}/", tag_namespace);
+            let image_tags = image_definition::list_image_tags(images_dir)
+                .map_err(RunBuildError::ListImagesError)?
+                .into_iter()
+                .filter(|tag| tag.starts_with(&tag_prefix))
+                .collect();
+
+            Ok(ImageSelection {
+                tag_namespaces: vec![tag_namespace],
+                image_tags,
+                downstream_tag: Some(image_tag),
+            })
+        } else {
+            Ok(ImageSelection {
+                tag_namespaces: vec![tag_namespace],
+                image_tags: vec![image_tag],
+                downstream_tag: None,
+            })
         }
     }
 }
 
+fn build_log_dir() -> Result<PathBuf, RunBuildError> {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or(0);
+
+    app_dirs::app_dir(
+        AppDataType::UserData,
+        &APP_INFO,
+        &format!("logs/{}-{}", timestamp, process::id()),
+    )
+    .map_err(RunBuildError::LogDirError)
+}
+
 impl Display for Build {
     fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
         write!(formatter, "build ")?;
 
         if let Some(images_dir) = &self.images_dir {
-            write!(formatter, "-d {} ", images_dir.display())?;
+            write!(
+                formatter,
+                "-d {} ",
+                shell_quote(&images_dir.display().to_string())
+            )?;
+        }
+
+        if self.force {
+            write!(formatter, "--force ")?;
+        }
+
+        if self.dry_run {
+            write!(formatter, "--dry-run ")?;
         }
 
-        write!(formatter, "{}", self.image_tag)
+        if self.keep_going {
+            write!(formatter, "--keep-going ")?;
+        }
+
+        if self.debug_on_failure {
+            write!(formatter, "--debug-on-failure ")?;
+        }
+
+        if let Some(keep_dockerfiles) = &self.keep_dockerfiles {
+            write!(
+                formatter,
+                "--keep-dockerfiles {} ",
+                shell_quote(&keep_dockerfiles.display().to_string())
+            )?;
+        }
+
+        if let Some(report) = &self.report {
+            write!(
+                formatter,
+                "--report {} ",
+                shell_quote(&report.display().to_string())
+            )?;
+        }
+
+        if self.push {
+            write!(formatter, "--push ")?;
+        }
+
+        if let Some(registry) = &self.registry {
+            write!(formatter, "--registry {} ", shell_quote(registry))?;
+        }
+
+        if self.jobs != 1 {
+            write!(formatter, "-j {} ", self.jobs)?;
+        }
+
+        if let Some(platform) = &self.platform {
+            write!(formatter, "--platform {} ", shell_quote(platform))?;
+        }
+
+        for (name, value) in &self.build_args {
+            write!(
+                formatter,
+                "--build-arg {} ",
+                shell_quote(&format!("{}={}", name, value))
+            )?;
+        }
+
+        for (name, value) in &self.variables {
+            write!(
+                formatter,
+                "--var {} ",
+                shell_quote(&format!("{}={}", name, value))
+            )?;
+        }
+
+        if self.no_cache {
+            write!(formatter, "--no-cache ")?;
+        }
+
+        if self.pull {
+            write!(formatter, "--pull ")?;
+        }
+
+        if let Some(network) = &self.network {
+            write!(formatter, "--network {} ", shell_quote(network))?;
+        }
+
+        if let Some(target) = &self.target {
+            write!(formatter, "--target {} ", shell_quote(target))?;
+        }
+
+        if let Some(progress) = &self.progress {
+            write!(formatter, "--progress {} ", shell_quote(progress))?;
+        }
+
+        for (name, value) in &self.labels {
+            write!(
+                formatter,
+                "--label {} ",
+                shell_quote(&format!("{}={}", name, value))
+            )?;
+        }
+
+        if let Some(downstream) = &self.downstream {
+            write!(formatter, "--downstream {} ", shell_quote(downstream))?;
+        }
+
+        if self.all {
+            write!(formatter, "--all ")?;
+        }
+
+        if let Some(filter) = &self.filter {
+            write!(formatter, "--filter {} ", shell_quote(filter))?;
+        }
+
+        if let Some(image_tag) = &self.image_tag {
+            write!(formatter, "{}", shell_quote(image_tag))?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{super::super::test_support::write_image, *};
+    use tempfile::TempDir;
+
+    fn parse(args: &[&str]) -> Build {
+        Build::from_iter(Some("build").iter().chain(args))
+    }
+
+    #[test]
+    fn build_arg_enables_conditional_item() {
+        let images_dir = TempDir::new().unwrap();
+
+        write_image(
+            &images_dir,
+            "ns/app",
+            "from: debian\ninstall:\n  - curl\n  - when: ssl\n    packages: [openssl]\n",
+        );
+
+        let build = parse(&["--build-arg", "ssl=1", "ns/app"]);
+        let mut definition =
+            image_definition::ImageDefinition::load(images_dir.path(), "ns/app").unwrap();
+
+        definition
+            .evaluate_conditions(&build.condition_variables())
+            .unwrap();
+        assert!(definition.to_string().contains("openssl"));
+    }
+
+    #[test]
+    fn var_takes_precedence_over_build_arg() {
+        let build = parse(&["--build-arg", "ssl=1", "--var", "ssl=0", "ns/app"]);
+
+        assert_eq!(build.condition_variables()["ssl"], "0");
+    }
+
+    #[test]
+    fn display_quotes_values_for_the_shell() {
+        let build = parse(&[
+            "--all",
+            "--filter",
+            "ns/*",
+            "--build-arg",
+            "MSG=it's here",
+            "--var",
+            "EMPTY=",
+            "--network",
+            "host",
+        ]);
+
+        assert_eq!(
+            build.to_string(),
+            "build --build-arg 'MSG=it'\\''s here' --var EMPTY= --network host --all --filter 'ns/*' "
+        );
     }
 }
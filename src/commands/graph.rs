@@ -0,0 +1,335 @@
+use super::{
+    super::{
+        build_graph::{BuildGraph, BuildGraphError},
+        config::Config,
+        docker::shell_quote,
+        dockerfile::Variables,
+    },
+    images_dir, parse_key_value,
+};
+use failure::Fail;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::{self, Display, Formatter},
+    path::PathBuf,
+    str::FromStr,
+};
+use structopt::StructOpt;
+
+#[derive(StructOpt)]
+pub struct Graph {
+    #[structopt(
+        name = "images directory",
+        short = "d",
+        long = "base-dir",
+        parse(from_os_str)
+    )]
+    images_dir: Option<PathBuf>,
+
+    /// Output format: tree, dot or mermaid
+    #[structopt(short = "f", long = "format", default_value = "tree")]
+    format: GraphFormat,
+
+    /// Variable available to conditions, as NAME=VALUE
+    #[structopt(
+        long = "var",
+        raw(number_of_values = "1"),
+        parse(try_from_str = "parse_key_value")
+    )]
+    variables: Vec<(String, String)>,
+}
+
+#[derive(Clone, Copy)]
+pub enum GraphFormat {
+    Tree,
+    Dot,
+    Mermaid,
+}
+
+#[derive(Debug, Fail)]
+pub enum RunGraphError {
+    #[fail(display = "Failed to resolve image dependencies")]
+    BuildGraphError(#[cause] BuildGraphError),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum EdgeKind {
+    From,
+    Copy,
+}
+
+struct DependencyGraph {
+    nodes: BTreeMap<String, bool>,
+    edges: BTreeSet<(String, String, EdgeKind)>,
+}
+
+impl Graph {
+    pub fn run(self, config: Config) -> Result<(), RunGraphError> {
+        let images_dir = images_dir(self.images_dir, &config);
+        let variables: Variables = self.variables.into_iter().collect();
+        let build_graph = BuildGraph::load_all(&images_dir, Some(&variables))
+            .map_err(RunGraphError::BuildGraphError)?;
+        let dependency_graph = DependencyGraph::new(&build_graph);
+
+        match self.format {
+            GraphFormat::Tree => dependency_graph.print_tree(),
+            GraphFormat::Dot => dependency_graph.print_dot(),
+            GraphFormat::Mermaid => dependency_graph.print_mermaid(),
+        }
+
+        Ok(())
+    }
+}
+
+impl DependencyGraph {
+    fn new(build_graph: &BuildGraph) -> Self {
+        let mut nodes = BTreeMap::new();
+        let mut edges = BTreeSet::new();
+
+        for index in 0..build_graph.len() {
+            let docker_image = build_graph.image(index);
+            let image_tag = docker_image.tag();
+            let base_images: Vec<&str> = docker_image.base_images().collect();
+            let copy_sources = docker_image
+                .copy_sources()
+                .filter(|source| !base_images.contains(source));
+
+            nodes.insert(image_tag.to_owned(), false);
+
+            let sources = base_images
+                .iter()
+                .map(|&source| (source, EdgeKind::From))
+                .chain(copy_sources.map(|source| (source, EdgeKind::Copy)));
+
+            for (source, kind) in sources {
+                let source = match build_graph.index(source) {
+                    Some(source_index) => build_graph.image(source_index).tag(),
+                    None => {
+                        nodes.entry(source.to_owned()).or_insert(true);
+                        source
+                    }
+                };
+
+                edges.insert((source.to_owned(), image_tag.to_owned(), kind));
+            }
+        }
+
+        DependencyGraph { nodes, edges }
+    }
+
+    fn dependents<'a>(&'a self, image: &'a str) -> impl Iterator<Item = (&'a str, EdgeKind)> {
+        self.edges
+            .iter()
+            .filter(move |(source, _, _)| source == image)
+            .map(|(_, dependent, kind)| (dependent.as_str(), *kind))
+    }
+
+    fn print_tree(&self) {
+        let mut reached = BTreeSet::new();
+        let roots = self.nodes.keys().filter(|image| {
+            !self
+                .edges
+                .iter()
+                .any(|(_, dependent, _)| dependent == *image)
+        });
+
+        for root in roots {
+            self.print_tree_node(root, EdgeKind::From, &mut Vec::new(), &mut reached);
+        }
+
+        for image in self.nodes.keys() {
+            if !reached.contains(image.as_str()) {
+                self.print_tree_node(image, EdgeKind::From, &mut Vec::new(), &mut reached);
+            }
+        }
+    }
+
+    fn print_tree_node<'a>(
+        &'a self,
+        image: &'a str,
+        kind: EdgeKind,
+        path: &mut Vec<&'a str>,
+        reached: &mut BTreeSet<&'a str>,
+    ) {
+        let mut markers = Vec::new();
+
+        if self.nodes[image] {
+            markers.push("external");
+        }
+
+        if kind == EdgeKind::Copy {
+            markers.push("copy");
+        }
+
+        if path.contains(&image) {
+            markers.push("cycle");
+        }
+
+        print!("{:indent$}{}", "", image, indent = path.len() * 2);
+
+        if markers.is_empty() {
+            println!();
+        } else {
+            println!(" ({})", markers.join(", "));
+        }
+
+        if path.contains(&image) {
+            return;
+        }
+
+        reached.insert(image);
+        path.push(image);
+
+        for (dependent, kind) in self.dependents(image) {
+            self.print_tree_node(dependent, kind, path, reached);
+        }
+
+        path.pop();
+    }
+
+    fn print_dot(&self) {
+        println!("digraph images {{");
+        println!("    rankdir=LR;");
+
+        for (image, &external) in &self.nodes {
+            if external {
+                println!("    {:?} [shape=box, style=dashed];", image);
+            } else {
+                println!("    {:?} [shape=box];", image);
+            }
+        }
+
+        for (source, dependent, kind) in &self.edges {
+            match kind {
+                EdgeKind::From => println!("    {:?} -> {:?};", source, dependent),
+                EdgeKind::Copy => println!(
+                    "    {:?} -> {:?} [style=dashed, label=\"copy\"];",
+                    source, dependent
+                ),
+            }
+        }
+
+        println!("}}");
+    }
+
+    fn print_mermaid(&self) {
+        let ids: BTreeMap<&str, String> = self
+            .nodes
+            .keys()
+            .enumerate()
+            .map(|(index, image)| (image.as_str(), format!("image{}", index)))
+            .collect();
+
+        println!("graph LR");
+
+        for (image, &external) in &self.nodes {
+            if external {
+                println!("    {}([\"{}\"]):::external", ids[image.as_str()], image);
+            } else {
+                println!("    {}[\"{}\"]", ids[image.as_str()], image);
+            }
+        }
+
+        for (source, dependent, kind) in &self.edges {
+            let arrow = match kind {
+                EdgeKind::From => "-->",
+                EdgeKind::Copy => "-. copy .->",
+            };
+
+            println!(
+                "    {} {} {}",
+                ids[source.as_str()],
+                arrow,
+                ids[dependent.as_str()]
+            );
+        }
+
+        println!("    classDef external stroke-dasharray: 5 5");
+    }
+}
+
+impl FromStr for GraphFormat {
+    type Err = String;
+
+    fn from_str(format: &str) -> Result<Self, Self::Err> {
+        match format {
+            "tree" => Ok(GraphFormat::Tree),
+            "dot" => Ok(GraphFormat::Dot),
+            "mermaid" => Ok(GraphFormat::Mermaid),
+            _ => Err(format!(
+                "Unknown graph format: {} (expected tree, dot or mermaid)",
+                format
+            )),
+        }
+    }
+}
+
+impl Display for GraphFormat {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        match self {
+            GraphFormat::Tree => write!(formatter, "tree"),
+            GraphFormat::Dot => write!(formatter, "dot"),
+            GraphFormat::Mermaid => write!(formatter, "mermaid"),
+        }
+    }
+}
+
+impl Display for Graph {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        write!(formatter, "graph ")?;
+
+        if let Some(images_dir) = &self.images_dir {
+            write!(
+                formatter,
+                "-d {} ",
+                shell_quote(&images_dir.display().to_string())
+            )?;
+        }
+
+        for (name, value) in &self.variables {
+            write!(
+                formatter,
+                "--var {} ",
+                shell_quote(&format!("{}={}", name, value))
+            )?;
+        }
+
+        write!(formatter, "--format {}", self.format)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{super::super::test_support::write_image, *};
+    use tempfile::TempDir;
+
+    #[test]
+    fn copy_only_dependency_is_a_copy_edge() {
+        let images_dir = TempDir::new().unwrap();
+
+        write_image(&images_dir, "ns/base", "from: alpine\n");
+        write_image(
+            &images_dir,
+            "ns/app",
+            "from: debian\ncopy:\n  - from: /usr/bin/tool\n    to: /usr/bin/tool\n    context: ns/base\n",
+        );
+
+        let build_graph = BuildGraph::load_all(images_dir.path(), Some(&Variables::new())).unwrap();
+        let dependency_graph = DependencyGraph::new(&build_graph);
+        let edge =
+            |source: &str, dependent: &str, kind| (source.to_owned(), dependent.to_owned(), kind);
+
+        assert_eq!(
+            dependency_graph.edges,
+            vec![
+                edge("alpine", "ns/base", EdgeKind::From),
+                edge("debian", "ns/app", EdgeKind::From),
+                edge("ns/base", "ns/app", EdgeKind::Copy),
+            ]
+            .into_iter()
+            .collect()
+        );
+        assert!(!dependency_graph.nodes["ns/base"]);
+        assert!(dependency_graph.nodes["debian"]);
+    }
+}
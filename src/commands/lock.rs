@@ -0,0 +1,132 @@
+use super::{
+    super::{
+        build_graph::{BuildGraph, BuildGraphError},
+        config::Config,
+        docker::{self, shell_quote},
+        image_reference::ImageReference,
+        lockfile::{Lockfile, LockfileError},
+    },
+    images_dir,
+};
+use failure::Fail;
+use std::{
+    fmt::{self, Display, Formatter},
+    io,
+    path::PathBuf,
+};
+use structopt::StructOpt;
+
+#[derive(StructOpt)]
+pub struct Lock {
+    #[structopt(
+        name = "images directory",
+        short = "d",
+        long = "base-dir",
+        parse(from_os_str)
+    )]
+    images_dir: Option<PathBuf>,
+
+    /// Resolve all base images again instead of keeping the pinned digests
+    #[structopt(long = "update")]
+    update: bool,
+}
+
+#[derive(Debug, Fail)]
+pub enum RunLockError {
+    #[fail(display = "Failed to resolve image dependencies")]
+    BuildGraphError(#[cause] BuildGraphError),
+
+    #[fail(display = "Failed to update lockfile")]
+    LockfileError(#[cause] LockfileError),
+
+    #[fail(display = "Failed to pull base image: {}", _0)]
+    PullError(String, #[cause] io::Error),
+
+    #[fail(display = "Failed to inspect base image: {}", _0)]
+    InspectError(String, #[cause] io::Error),
+
+    #[fail(display = "No repository digest found for base image: {}", _0)]
+    MissingDigest(String),
+}
+
+impl Lock {
+    pub fn run(self, config: Config) -> Result<(), RunLockError> {
+        let images_dir = images_dir(self.images_dir, &config);
+        let build_graph =
+            BuildGraph::load_all(&images_dir, None).map_err(RunLockError::BuildGraphError)?;
+        let mut lockfile = if self.update {
+            Lockfile::default()
+        } else {
+            Lockfile::load(&images_dir).map_err(RunLockError::LockfileError)?
+        };
+        let base_images: Vec<&str> = build_graph
+            .external_images()
+            .into_iter()
+            .filter(|image| is_pinnable(image))
+            .collect();
+
+        for image in lockfile.retain(&base_images) {
+            println!("Removed unused pin of {}", image);
+        }
+
+        for image in base_images {
+            if !lockfile.contains(image) {
+                let digest = resolve_digest(image)?;
+
+                println!("Pinned {} to {}", image, digest);
+                lockfile.insert(image, digest);
+            }
+        }
+
+        lockfile
+            .save(&images_dir)
+            .map_err(RunLockError::LockfileError)
+    }
+}
+
+fn is_pinnable(image: &str) -> bool {
+    image != "scratch" && !image.contains('@') && !image.contains('$')
+}
+
+fn resolve_digest(image: &str) -> Result<String, RunLockError> {
+    docker::pull()
+        .pull(image)
+        .map_err(|error| RunLockError::PullError(image.to_owned(), error))?;
+
+    let repository = match image.parse::<ImageReference>() {
+        Ok(reference) => reference.without_version().to_string(),
+        Err(_) => image.to_owned(),
+    };
+    let repo_digests = docker::inspect()
+        .repo_digests(image)
+        .map_err(|error| RunLockError::InspectError(image.to_owned(), error))?;
+    let repo_digest = repo_digests
+        .iter()
+        .find(|repo_digest| repo_digest.split('@').next() == Some(repository.as_str()))
+        .or_else(|| repo_digests.first());
+
+    repo_digest
+        .and_then(|repo_digest| repo_digest.split('@').nth(1))
+        .map(str::to_owned)
+        .ok_or_else(|| RunLockError::MissingDigest(image.to_owned()))
+}
+
+impl Display for Lock {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        write!(formatter, "lock")?;
+
+        if let Some(images_dir) = &self.images_dir {
+            write!(
+                formatter,
+                " -d {}",
+                shell_quote(&images_dir.display().to_string())
+            )?;
+        }
+
+        if self.update {
+            write!(formatter, " --update")?;
+        }
+
+        Ok(())
+    }
+}
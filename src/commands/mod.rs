@@ -1,20 +1,34 @@
 mod build;
 mod clean;
+mod graph;
+mod lock;
 mod new;
+mod push;
+mod render;
 mod run;
+mod test;
 
 pub use self::{
     build::{Build, RunBuildError},
     clean::{Clean, RunCleanError},
+    graph::{Graph, RunGraphError},
+    lock::{Lock, RunLockError},
     new::{New, RunNewError},
+    push::{Push, RunPushError},
+    render::{Render, RunRenderError},
     run::{Run, RunRunError},
+    test::{RunTestError, Test},
 };
 use super::config::Config;
 use failure::Fail;
-use std::fmt::{self, Display, Formatter};
+use std::{
+    fmt::{self, Display, Formatter},
+    path::PathBuf,
+};
 use structopt::StructOpt;
 
 #[derive(StructOpt)]
+#[allow(clippy::large_enum_variant)]
 pub enum Commands {
     #[structopt(name = "build")]
     Build(Build),
@@ -22,11 +36,26 @@ pub enum Commands {
     #[structopt(name = "clean")]
     Clean(Clean),
 
+    #[structopt(name = "graph")]
+    Graph(Graph),
+
+    #[structopt(name = "lock")]
+    Lock(Lock),
+
     #[structopt(name = "new")]
     New(New),
 
+    #[structopt(name = "push")]
+    Push(Push),
+
+    #[structopt(name = "render")]
+    Render(Render),
+
     #[structopt(name = "run")]
     Run(Run),
+
+    #[structopt(name = "test")]
+    Test(Test),
 }
 
 #[derive(Debug, Fail)]
@@ -37,11 +66,26 @@ pub enum RunCommandError {
     #[fail(display = "Failed to remove stale images")]
     Clean(#[cause] RunCleanError),
 
+    #[fail(display = "Failed to print image dependency graph")]
+    Graph(#[cause] RunGraphError),
+
+    #[fail(display = "Failed to pin base images")]
+    Lock(#[cause] RunLockError),
+
     #[fail(display = "Failed to create new project")]
     New(#[cause] RunNewError),
 
+    #[fail(display = "Failed to push image")]
+    Push(#[cause] RunPushError),
+
+    #[fail(display = "Failed to render image")]
+    Render(#[cause] RunRenderError),
+
     #[fail(display = "Failed to run project environment")]
     Run(#[cause] RunRunError),
+
+    #[fail(display = "Failed to test image")]
+    Test(#[cause] RunTestError),
 }
 
 impl Commands {
@@ -49,8 +93,13 @@ impl Commands {
         match self {
             Commands::Build(build) => build.run(config).map_err(RunCommandError::Build),
             Commands::Clean(clean) => clean.run().map_err(RunCommandError::Clean),
+            Commands::Graph(graph) => graph.run(config).map_err(RunCommandError::Graph),
+            Commands::Lock(lock) => lock.run(config).map_err(RunCommandError::Lock),
             Commands::New(new) => new.run().map_err(RunCommandError::New),
+            Commands::Push(push) => push.run(config).map_err(RunCommandError::Push),
+            Commands::Render(render) => render.run(config).map_err(RunCommandError::Render),
             Commands::Run(run) => run.run().map_err(RunCommandError::Run),
+            Commands::Test(test) => test.run(config).map_err(RunCommandError::Test),
         }
     }
 }
@@ -60,8 +109,40 @@ impl Display for Commands {
         match self {
             Commands::Build(build) => build.fmt(formatter),
             Commands::Clean(clean) => clean.fmt(formatter),
+            Commands::Graph(graph) => graph.fmt(formatter),
+            Commands::Lock(lock) => lock.fmt(formatter),
             Commands::New(new) => new.fmt(formatter),
+            Commands::Push(push) => push.fmt(formatter),
+            Commands::Render(render) => render.fmt(formatter),
             Commands::Run(run) => run.fmt(formatter),
+            Commands::Test(test) => test.fmt(formatter),
         }
     }
 }
+
+fn images_dir(images_dir: Option<PathBuf>, config: &Config) -> PathBuf {
+    match images_dir {
+        Some(path) => path,
+        None => PathBuf::from(match &config.images_dir {
+            Some(path) => path.as_str(),
+            None => "/project",
+        }),
+    }
+}
+
+fn tag_namespace(image_tag: &str, config: &Config) -> Option<String> {
+    match &config.tag_namespace {
+        Some(namespace) => Some(namespace.clone()),
+        None => image_tag.find('/').map(|end| image_tag[0..end].to_owned()),
+    }
+}
+
+fn parse_key_value(argument: &str) -> Result<(String, String), String> {
+    match argument.find('=') {
+        Some(position) => Ok((
+            argument[..position].to_owned(),
+            argument[(position + 1)..].to_owned(),
+        )),
+        None => Err(format!("Expected NAME=VALUE, got: {}", argument)),
+    }
+}
@@ -0,0 +1,128 @@
+use super::{
+    super::{
+        build_graph::{BuildGraph, BuildGraphError},
+        config::Config,
+        docker::shell_quote,
+        docker_image::PushDockerImageError,
+        dockerfile::Variables,
+    },
+    images_dir, parse_key_value, tag_namespace,
+};
+use failure::Fail;
+use std::{
+    fmt::{self, Display, Formatter},
+    path::PathBuf,
+};
+use structopt::StructOpt;
+
+#[derive(StructOpt)]
+pub struct Push {
+    #[structopt(
+        name = "images directory",
+        short = "d",
+        long = "base-dir",
+        parse(from_os_str)
+    )]
+    images_dir: Option<PathBuf>,
+
+    /// Registry to push the images to, instead of the one in the config file
+    #[structopt(long = "registry")]
+    registry: Option<String>,
+
+    /// Also push the images the requested image depends on, in dependency order
+    #[structopt(long = "with-dependencies")]
+    with_dependencies: bool,
+
+    /// Variable available to conditions, as NAME=VALUE
+    #[structopt(
+        long = "var",
+        raw(number_of_values = "1"),
+        parse(try_from_str = "parse_key_value")
+    )]
+    variables: Vec<(String, String)>,
+
+    image_tag: String,
+}
+
+#[derive(Debug, Fail)]
+pub enum RunPushError {
+    #[fail(
+        display = "Missing image tag namespace in the config file or in the image tag: {}",
+        _0
+    )]
+    NoTagNamespace(String),
+
+    #[fail(display = "Failed to resolve image dependencies")]
+    BuildGraphError(#[cause] BuildGraphError),
+
+    #[fail(display = "Failed to push docker image")]
+    PushImageError(#[cause] PushDockerImageError),
+}
+
+impl Push {
+    pub fn run(self, config: Config) -> Result<(), RunPushError> {
+        let images_dir = images_dir(self.images_dir, &config);
+        let tag_namespace = match tag_namespace(&self.image_tag, &config) {
+            Some(namespace) => namespace,
+            None => return Err(RunPushError::NoTagNamespace(self.image_tag)),
+        };
+        let variables: Variables = self.variables.into_iter().collect();
+        let registry = self.registry.or(config.registry);
+
+        let build_graph = BuildGraph::load(
+            &images_dir,
+            &[tag_namespace],
+            vec![self.image_tag.clone()],
+            Some(&variables),
+        )
+        .map_err(RunPushError::BuildGraphError)?;
+        let build_order = if self.with_dependencies {
+            build_graph
+                .build_order()
+                .map_err(RunPushError::BuildGraphError)?
+        } else {
+            build_graph.index(&self.image_tag).into_iter().collect()
+        };
+
+        for index in build_order {
+            build_graph
+                .image(index)
+                .push(registry.as_deref())
+                .map_err(RunPushError::PushImageError)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Display for Push {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        write!(formatter, "push ")?;
+
+        if let Some(images_dir) = &self.images_dir {
+            write!(
+                formatter,
+                "-d {} ",
+                shell_quote(&images_dir.display().to_string())
+            )?;
+        }
+
+        if let Some(registry) = &self.registry {
+            write!(formatter, "--registry {} ", shell_quote(registry))?;
+        }
+
+        if self.with_dependencies {
+            write!(formatter, "--with-dependencies ")?;
+        }
+
+        for (name, value) in &self.variables {
+            write!(
+                formatter,
+                "--var {} ",
+                shell_quote(&format!("{}={}", name, value))
+            )?;
+        }
+
+        write!(formatter, "{}", shell_quote(&self.image_tag))
+    }
+}
@@ -0,0 +1,95 @@
+use super::{
+    super::{
+        config::Config,
+        docker::shell_quote,
+        docker_image::{DockerImage, NewDockerImageError},
+        dockerfile::Variables,
+    },
+    images_dir, parse_key_value, tag_namespace,
+};
+use failure::Fail;
+use std::{
+    fmt::{self, Display, Formatter},
+    path::PathBuf,
+};
+use structopt::StructOpt;
+
+#[derive(StructOpt)]
+pub struct Render {
+    #[structopt(
+        name = "images directory",
+        short = "d",
+        long = "base-dir",
+        parse(from_os_str)
+    )]
+    images_dir: Option<PathBuf>,
+
+    /// Variable available to conditions, as NAME=VALUE
+    #[structopt(
+        long = "var",
+        raw(number_of_values = "1"),
+        parse(try_from_str = "parse_key_value")
+    )]
+    variables: Vec<(String, String)>,
+
+    image_tag: String,
+}
+
+#[derive(Debug, Fail)]
+pub enum RunRenderError {
+    #[fail(
+        display = "Missing image tag namespace in the config file or in the image tag: {}",
+        _0
+    )]
+    NoTagNamespace(String),
+
+    #[fail(display = "Failed to parse image description")]
+    NewDockerImageError(#[cause] NewDockerImageError),
+}
+
+impl Render {
+    pub fn run(self, config: Config) -> Result<(), RunRenderError> {
+        let images_dir = images_dir(self.images_dir, &config);
+        let tag_namespace = match tag_namespace(&self.image_tag, &config) {
+            Some(namespace) => namespace,
+            None => return Err(RunRenderError::NoTagNamespace(self.image_tag)),
+        };
+        let variables: Variables = self.variables.into_iter().collect();
+
+        let docker_image = DockerImage::new(
+            &images_dir,
+            &self.image_tag,
+            &tag_namespace,
+            Some(&variables),
+        )
+        .map_err(RunRenderError::NewDockerImageError)?;
+
+        print!("{}", docker_image.definition());
+
+        Ok(())
+    }
+}
+
+impl Display for Render {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        write!(formatter, "render ")?;
+
+        if let Some(images_dir) = &self.images_dir {
+            write!(
+                formatter,
+                "-d {} ",
+                shell_quote(&images_dir.display().to_string())
+            )?;
+        }
+
+        for (name, value) in &self.variables {
+            write!(
+                formatter,
+                "--var {} ",
+                shell_quote(&format!("{}={}", name, value))
+            )?;
+        }
+
+        write!(formatter, "{}", shell_quote(&self.image_tag))
+    }
+}
@@ -0,0 +1,107 @@
+use super::{
+    super::{
+        build_graph::{BuildGraph, BuildGraphError},
+        config::Config,
+        docker::shell_quote,
+        docker_image::TestDockerImageError,
+        dockerfile::Variables,
+    },
+    images_dir, parse_key_value, tag_namespace,
+};
+use failure::Fail;
+use std::{
+    fmt::{self, Display, Formatter},
+    path::PathBuf,
+};
+use structopt::StructOpt;
+
+#[derive(StructOpt)]
+pub struct Test {
+    #[structopt(
+        name = "images directory",
+        short = "d",
+        long = "base-dir",
+        parse(from_os_str)
+    )]
+    images_dir: Option<PathBuf>,
+
+    /// Variable available to conditions, as NAME=VALUE
+    #[structopt(
+        long = "var",
+        raw(number_of_values = "1"),
+        parse(try_from_str = "parse_key_value")
+    )]
+    variables: Vec<(String, String)>,
+
+    image_tag: String,
+}
+
+#[derive(Debug, Fail)]
+pub enum RunTestError {
+    #[fail(
+        display = "Missing image tag namespace in the config file or in the image tag: {}",
+        _0
+    )]
+    NoTagNamespace(String),
+
+    #[fail(display = "Failed to load image")]
+    BuildGraphError(#[cause] BuildGraphError),
+
+    #[fail(display = "Image not found in images directory: {}", _0)]
+    UnknownImage(String),
+
+    #[fail(display = "Docker image failed its tests")]
+    TestImageError(#[cause] TestDockerImageError),
+}
+
+impl Test {
+    pub fn run(self, config: Config) -> Result<(), RunTestError> {
+        let images_dir = images_dir(self.images_dir, &config);
+        let tag_namespace = match tag_namespace(&self.image_tag, &config) {
+            Some(namespace) => namespace,
+            None => return Err(RunTestError::NoTagNamespace(self.image_tag)),
+        };
+        let variables: Variables = self.variables.into_iter().collect();
+
+        let build_graph = BuildGraph::load(
+            &images_dir,
+            &[tag_namespace],
+            vec![self.image_tag.clone()],
+            Some(&variables),
+        )
+        .map_err(RunTestError::BuildGraphError)?;
+        let index = match build_graph.index(&self.image_tag) {
+            Some(index) => index,
+            None => return Err(RunTestError::UnknownImage(self.image_tag)),
+        };
+
+        build_graph
+            .image(index)
+            .test()
+            .map_err(RunTestError::TestImageError)
+    }
+}
+
+impl Display for Test {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        write!(formatter, "test ")?;
+
+        if let Some(images_dir) = &self.images_dir {
+            write!(
+                formatter,
+                "-d {} ",
+                shell_quote(&images_dir.display().to_string())
+            )?;
+        }
+
+        for (name, value) in &self.variables {
+            write!(
+                formatter,
+                "--var {} ",
+                shell_quote(&format!("{}={}", name, value))
+            )?;
+        }
+
+        write!(formatter, "{}", shell_quote(&self.image_tag))
+    }
+}
@@ -8,6 +8,8 @@ use std::{fs::File, io::BufReader};
 pub struct Config {
     pub tag_namespace: Option<String>,
     pub images_dir: Option<String>,
+    pub registry: Option<String>,
+    pub keep_dockerfiles: Option<String>,
 }
 
 impl Config {
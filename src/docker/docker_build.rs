@@ -0,0 +1,106 @@
+use super::docker_command::DockerCommand;
+use std::{
+    borrow::Cow,
+    fmt::{self, Display, Formatter},
+    io,
+};
+
+pub struct DockerBuild<'a> {
+    context: Cow<'a, str>,
+    command: DockerCommand<'a>,
+}
+
+impl<'a> DockerBuild<'a> {
+    pub fn new(context: impl Into<Cow<'a, str>>) -> Self {
+        let mut command = DockerCommand::new();
+        command.env("DOCKER_BUILDKIT", "1").append("build");
+
+        DockerBuild {
+            context: context.into(),
+            command,
+        }
+    }
+
+    pub fn tag(&mut self, tag: impl Into<Cow<'a, str>>) -> &mut Self {
+        self.command.append("-t").append(tag);
+        self
+    }
+
+    pub fn dockerfile(&mut self, dockerfile: impl Into<Cow<'a, str>>) -> &mut Self {
+        self.command.append("-f").append(dockerfile);
+        self
+    }
+
+    pub fn platform(&mut self, platform: impl Into<Cow<'a, str>>) -> &mut Self {
+        self.command.append("--platform").append(platform);
+        self
+    }
+
+    pub fn label(&mut self, name: impl AsRef<str>, value: impl AsRef<str>) -> &mut Self {
+        self.command
+            .append("--label")
+            .append(format!("{}={}", name.as_ref(), value.as_ref()));
+        self
+    }
+
+    pub fn build_arg(&mut self, name: impl AsRef<str>, value: impl AsRef<str>) -> &mut Self {
+        self.command
+            .append("--build-arg")
+            .append(format!("{}={}", name.as_ref(), value.as_ref()));
+        self
+    }
+
+    pub fn no_cache(&mut self) -> &mut Self {
+        self.command.append("--no-cache");
+        self
+    }
+
+    pub fn pull(&mut self) -> &mut Self {
+        self.command.append("--pull");
+        self
+    }
+
+    pub fn network(&mut self, network: impl Into<Cow<'a, str>>) -> &mut Self {
+        self.command.append("--network").append(network);
+        self
+    }
+
+    pub fn target(&mut self, target: impl Into<Cow<'a, str>>) -> &mut Self {
+        self.command.append("--target").append(target);
+        self
+    }
+
+    pub fn progress(&mut self, progress: impl Into<Cow<'a, str>>) -> &mut Self {
+        self.command.append("--progress").append(progress);
+        self
+    }
+
+    pub fn build_context(&mut self, name: impl AsRef<str>, value: impl AsRef<str>) -> &mut Self {
+        self.command.append("--build-context").append(format!(
+            "{}={}",
+            name.as_ref(),
+            value.as_ref()
+        ));
+        self
+    }
+
+    pub fn run(self) -> Result<(), io::Error> {
+        let mut command = self.command;
+
+        command.append(self.context);
+        command.run()
+    }
+
+    pub fn run_with_output_handler(self, handle_line: impl FnMut(&str)) -> Result<(), io::Error> {
+        let mut command = self.command;
+
+        command.append(self.context);
+        command.run_with_output_handler(handle_line)
+    }
+}
+
+impl<'a> Display for DockerBuild<'a> {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        write!(formatter, "{} {}", self.command, self.context)
+    }
+}
@@ -1,17 +1,21 @@
-use duct::cmd;
+use super::shell_quote;
+use duct::{cmd, Expression};
 use std::{
     borrow::{Borrow, Cow},
     ffi::OsStr,
-    io,
+    fmt::{self, Display, Formatter},
+    io::{self, BufRead, BufReader},
 };
 
 pub struct DockerCommand<'a> {
+    environment: Vec<(&'static str, &'static str)>,
     arguments: Vec<Cow<'a, str>>,
 }
 
 impl<'a> DockerCommand<'a> {
     pub fn new() -> Self {
         DockerCommand {
+            environment: Vec::new(),
             arguments: Vec::new(),
         }
     }
@@ -21,15 +25,91 @@ impl<'a> DockerCommand<'a> {
         self
     }
 
+    pub fn env(&mut self, name: &'static str, value: &'static str) -> &mut Self {
+        self.environment.push((name, value));
+        self
+    }
+
     pub fn run(self) -> Result<(), io::Error> {
-        cmd(
+        self.expression().run().map(|_| ())
+    }
+
+    pub fn read(self) -> Result<String, io::Error> {
+        self.expression().stderr_null().read()
+    }
+
+    pub fn status(self) -> Result<i32, io::Error> {
+        let output = self.expression().unchecked().run()?;
+
+        Ok(output.status.code().unwrap_or(-1))
+    }
+
+    pub fn output(self) -> Result<(i32, String), io::Error> {
+        let output = self
+            .expression()
+            .stderr_to_stdout()
+            .stdout_capture()
+            .unchecked()
+            .run()?;
+
+        Ok((
+            output.status.code().unwrap_or(-1),
+            String::from_utf8_lossy(&output.stdout).into_owned(),
+        ))
+    }
+
+    pub fn run_with_output_handler(
+        self,
+        mut handle_line: impl FnMut(&str),
+    ) -> Result<(), io::Error> {
+        let (reader, writer) = os_pipe::pipe()?;
+        let handle = {
+            let expression = self.expression().stderr_to_stdout().stdout_handle(writer);
+
+            expression.start()?
+        };
+        let mut reader = BufReader::new(reader);
+        let mut line = Vec::new();
+
+        while reader.read_until(b'\n', &mut line)? > 0 {
+            let line_string = String::from_utf8_lossy(&line);
+
+            handle_line(line_string.trim_end_matches(&['\r', '\n'][..]));
+            line.clear();
+        }
+
+        handle.wait().map(|_| ())
+    }
+
+    fn expression(&self) -> Expression {
+        let expression = cmd(
             "docker",
             self.arguments.iter().map(|argument| {
-                let argument_str: &'a str = argument.borrow();
+                let argument_str: &str = argument.borrow();
                 OsStr::new(argument_str)
             }),
-        )
-        .run()
-        .map(|_| ())
+        );
+
+        self.environment
+            .iter()
+            .fold(expression, |expression, (name, value)| {
+                expression.env(name, value)
+            })
+    }
+}
+
+impl<'a> Display for DockerCommand<'a> {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        for (name, value) in &self.environment {
+            write!(formatter, "{}={} ", name, value)?;
+        }
+
+        write!(formatter, "docker")?;
+
+        for argument in &self.arguments {
+            write!(formatter, " {}", shell_quote(argument))?;
+        }
+
+        Ok(())
     }
 }
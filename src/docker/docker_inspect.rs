@@ -0,0 +1,109 @@
+use super::docker_command::DockerCommand;
+use std::io;
+
+pub struct DockerInspect;
+
+impl DockerInspect {
+    pub fn new() -> Self {
+        DockerInspect
+    }
+
+    pub fn image_label(
+        self,
+        image: impl AsRef<str>,
+        label: impl AsRef<str>,
+    ) -> Result<Option<String>, io::Error> {
+        let mut command = DockerCommand::new();
+
+        command
+            .append("image")
+            .append("inspect")
+            .append("--format")
+            .append(format!(
+                r#"{{{{ index .Config.Labels "{}" }}}}"#,
+                label.as_ref()
+            ))
+            .append(image.as_ref());
+
+        let value = command.read()?;
+
+        if value.is_empty() || value == "<no value>" {
+            Ok(None)
+        } else {
+            Ok(Some(value))
+        }
+    }
+
+    pub fn repo_digests(self, image: impl AsRef<str>) -> Result<Vec<String>, io::Error> {
+        let mut command = DockerCommand::new();
+
+        command
+            .append("image")
+            .append("inspect")
+            .append("--format")
+            .append("{{ range .RepoDigests }}{{ println . }}{{ end }}")
+            .append(image.as_ref());
+
+        let output = command.read()?;
+
+        Ok(output
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(str::to_owned)
+            .collect())
+    }
+
+    pub fn image_id_and_size(self, image: impl AsRef<str>) -> Result<(String, u64), io::Error> {
+        let mut command = DockerCommand::new();
+
+        command
+            .append("image")
+            .append("inspect")
+            .append("--format")
+            .append("{{ .Id }} {{ .Size }}")
+            .append(image.as_ref());
+
+        let output = command.read()?;
+        let mut fields = output.split_whitespace();
+        let id = fields.next().unwrap_or("").to_owned();
+        let size = fields
+            .next()
+            .and_then(|size| size.parse().ok())
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, output.clone()))?;
+
+        Ok((id, size))
+    }
+
+    pub fn image_env(self, image: impl AsRef<str>) -> Result<Vec<String>, io::Error> {
+        let mut command = DockerCommand::new();
+
+        command
+            .append("image")
+            .append("inspect")
+            .append("--format")
+            .append("{{ range .Config.Env }}{{ println . }}{{ end }}")
+            .append(image.as_ref());
+
+        let output = command.read()?;
+
+        Ok(output
+            .lines()
+            .filter(|line| !line.is_empty())
+            .map(str::to_owned)
+            .collect())
+    }
+
+    pub fn image_user(self, image: impl AsRef<str>) -> Result<String, io::Error> {
+        let mut command = DockerCommand::new();
+
+        command
+            .append("image")
+            .append("inspect")
+            .append("--format")
+            .append("{{ .Config.User }}")
+            .append(image.as_ref());
+
+        command.read()
+    }
+}
@@ -0,0 +1,18 @@
+use super::docker_command::DockerCommand;
+use std::io;
+
+pub struct DockerPull;
+
+impl DockerPull {
+    pub fn new() -> Self {
+        DockerPull
+    }
+
+    pub fn pull(self, image: impl AsRef<str>) -> Result<(), io::Error> {
+        let mut command = DockerCommand::new();
+
+        command.append("pull").append(image.as_ref());
+
+        command.run()
+    }
+}
@@ -0,0 +1,18 @@
+use super::docker_command::DockerCommand;
+use std::io;
+
+pub struct DockerPush;
+
+impl DockerPush {
+    pub fn new() -> Self {
+        DockerPush
+    }
+
+    pub fn push(self, image: impl AsRef<str>) -> Result<(), io::Error> {
+        let mut command = DockerCommand::new();
+
+        command.append("push").append(image.as_ref());
+
+        command.run()
+    }
+}
@@ -0,0 +1,22 @@
+use super::docker_command::DockerCommand;
+use std::io;
+
+pub struct DockerRmi;
+
+impl DockerRmi {
+    pub fn new() -> Self {
+        DockerRmi
+    }
+
+    pub fn remove<'a>(self, images: impl IntoIterator<Item = &'a str>) -> Result<(), io::Error> {
+        let mut command = DockerCommand::new();
+
+        command.append("rmi");
+
+        for image in images {
+            command.append(image.to_owned());
+        }
+
+        command.run()
+    }
+}
@@ -34,7 +34,11 @@ impl<'a> DockerRun<'a> {
         self
     }
 
-    pub fn read_only_volume(&mut self, source: impl AsRef<str>, target: impl AsRef<str>) -> &mut Self {
+    pub fn read_only_volume(
+        &mut self,
+        source: impl AsRef<str>,
+        target: impl AsRef<str>,
+    ) -> &mut Self {
         self.command
             .append("-v")
             .append(format!("{}:{}:ro", source.as_ref(), target.as_ref()));
@@ -66,4 +70,30 @@ impl<'a> DockerRun<'a> {
 
         command.run()
     }
+
+    pub fn run_shell(self) -> Result<i32, io::Error> {
+        let mut command = self.command;
+
+        command
+            .append("--entrypoint")
+            .append("sh")
+            .append(self.image);
+        command.status()
+    }
+
+    pub fn shell_command_output(
+        self,
+        shell_command: impl AsRef<str>,
+    ) -> Result<(i32, String), io::Error> {
+        let mut command = self.command;
+
+        command
+            .append("--entrypoint")
+            .append("sh")
+            .append(self.image)
+            .append("-c")
+            .append(shell_command.as_ref().to_owned());
+
+        command.output()
+    }
 }
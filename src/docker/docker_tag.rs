@@ -0,0 +1,21 @@
+use super::docker_command::DockerCommand;
+use std::io;
+
+pub struct DockerTag;
+
+impl DockerTag {
+    pub fn new() -> Self {
+        DockerTag
+    }
+
+    pub fn tag(self, source: impl AsRef<str>, target: impl AsRef<str>) -> Result<(), io::Error> {
+        let mut command = DockerCommand::new();
+
+        command
+            .append("tag")
+            .append(source.as_ref())
+            .append(target.as_ref());
+
+        command.run()
+    }
+}
@@ -1,14 +1,58 @@
+mod docker_build;
 mod docker_command;
+mod docker_inspect;
+mod docker_pull;
+mod docker_push;
+mod docker_rmi;
 mod docker_run;
+mod docker_tag;
 mod docker_volume;
 
-pub use self::{docker_run::DockerRun, docker_volume::DockerVolume};
+pub use self::{
+    docker_build::DockerBuild, docker_inspect::DockerInspect, docker_pull::DockerPull,
+    docker_push::DockerPush, docker_rmi::DockerRmi, docker_run::DockerRun, docker_tag::DockerTag,
+    docker_volume::DockerVolume,
+};
 use std::borrow::Cow;
 
+pub fn build<'a>(context: impl Into<Cow<'a, str>>) -> DockerBuild<'a> {
+    DockerBuild::new(context)
+}
+
+pub fn inspect() -> DockerInspect {
+    DockerInspect::new()
+}
+
+pub fn pull() -> DockerPull {
+    DockerPull::new()
+}
+
+pub fn push() -> DockerPush {
+    DockerPush::new()
+}
+
+pub fn rmi() -> DockerRmi {
+    DockerRmi::new()
+}
+
 pub fn run<'a>(image: impl Into<Cow<'a, str>>) -> DockerRun<'a> {
     DockerRun::new(image)
 }
 
+pub fn tag() -> DockerTag {
+    DockerTag::new()
+}
+
 pub fn volume() -> DockerVolume {
     DockerVolume::new()
 }
+
+pub fn shell_quote(argument: &str) -> Cow<'_, str> {
+    if argument.is_empty()
+        || argument.contains(|c: char| c.is_whitespace() || "'\"$`\\*?[]{}~#;&|<>()!".contains(c))
+    {
+        Cow::Owned(format!("'{}'", argument.replace('\'', "'\\''")))
+    } else {
+        Cow::Borrowed(argument)
+    }
+}
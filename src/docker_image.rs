@@ -1,17 +1,48 @@
-use super::dockerfile::{self, Dockerfile};
-use duct::cmd;
+use super::{
+    build_log,
+    docker::{self, DockerBuild},
+    dockerfile::{self, Variables},
+    fingerprint::Fingerprint,
+    image_definition::{ImageDefinition, LoadImageDefinitionError},
+    image_reference::ImageReference,
+    lockfile::Lockfile,
+};
 use failure::Fail;
+use sha2::{Digest, Sha256};
 use std::{
+    borrow::Cow,
+    fs::{self, File, OpenOptions},
     io::{self, Write},
+    iter,
     path::{Path, PathBuf},
 };
-use tempfile::NamedTempFile;
+use tempfile::TempDir;
+
+const FINGERPRINT_LABEL: &str = "dkr.fingerprint";
 
 #[derive(Debug)]
 pub struct DockerImage {
     tag: String,
-    dockerfile: Dockerfile,
+    tags: Vec<String>,
+    definition: ImageDefinition,
     source_directory: PathBuf,
+    image_directory: PathBuf,
+    named_contexts: Vec<(String, String)>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct BuildOptions {
+    pub platform: Option<String>,
+    pub build_args: Vec<(String, String)>,
+    pub prefix_output: bool,
+    pub no_cache: bool,
+    pub pull: bool,
+    pub network: Option<String>,
+    pub target: Option<String>,
+    pub progress: Option<String>,
+    pub labels: Vec<(String, String)>,
+    pub log_dir: Option<PathBuf>,
+    pub keep_dockerfiles: Option<PathBuf>,
 }
 
 #[derive(Debug, Fail)]
@@ -20,13 +51,16 @@ pub enum NewDockerImageError {
     InvalidTag(String),
 
     #[fail(display = "Failed to load Dockerfile for image: {}", _0)]
-    DockerfileError(String, #[cause] dockerfile::FromFileError),
+    DockerfileError(String, #[cause] LoadImageDefinitionError),
+
+    #[fail(display = "Failed to evaluate conditions for image: {}", _0)]
+    ConditionError(String, #[cause] dockerfile::DisabledStageError),
 }
 
 #[derive(Debug, Fail)]
 pub enum BuildDockerImageError {
     #[fail(
-        display = "Failed to create file to write Dockerfile for image: {}",
+        display = "Failed to create directory to write Dockerfile for image: {}",
         _0
     )]
     CreateDockerfileError(String, #[cause] io::Error),
@@ -34,77 +68,845 @@ pub enum BuildDockerImageError {
     #[fail(display = "Failed to write Dockerfile contents for image: {}", _0)]
     WriteDockerfileError(String, #[cause] io::Error),
 
+    #[fail(display = "Failed to write .dockerignore contents for image: {}", _0)]
+    WriteDockerignoreError(String, #[cause] io::Error),
+
+    #[fail(display = "Failed to create build log file for image: {}", _0)]
+    CreateLogError(String, #[cause] io::Error),
+
     #[fail(display = "Failed to run docker command to build image: {}", _0)]
     DockerCommandError(String, #[cause] io::Error),
+
+    #[fail(
+        display = "Failed to run docker command to build image: {} (see log: {})",
+        _0, _1
+    )]
+    LoggedDockerCommandError(String, String, #[cause] io::Error),
+
+    #[fail(display = "Failed to build image {} at {} (see log: {})", _0, _1, _2)]
+    FailedInstructionError(String, String, String, #[cause] io::Error),
+}
+
+#[derive(Debug, Fail)]
+pub enum DebugDockerImageError {
+    #[fail(
+        display = "No build log to find the failed instruction of image: {}",
+        _0
+    )]
+    MissingBuildLog(String),
+
+    #[fail(display = "Failed to read build log of image: {}", _0)]
+    ReadLogError(String, #[cause] io::Error),
+
+    #[fail(
+        display = "Failed to find the instruction that failed to build image: {}",
+        _0
+    )]
+    UnknownFailedInstruction(String),
+
+    #[fail(
+        display = "Image {} failed on its first instruction, there is nothing to debug: {}",
+        _0, _1
+    )]
+    NothingToDebug(String, String),
+
+    #[fail(display = "Failed to build image up to its failed instruction")]
+    BuildError(#[cause] BuildDockerImageError),
+
+    #[fail(display = "Failed to start debugging shell for image: {}", _0)]
+    ShellError(String, #[cause] io::Error),
+}
+
+#[derive(Debug, Fail)]
+pub enum PushDockerImageError {
+    #[fail(display = "Failed to tag image {} for registry as: {}", _0, _1)]
+    TagCommandError(String, String, #[cause] io::Error),
+
+    #[fail(display = "Failed to push image: {}", _0)]
+    PushCommandError(String, #[cause] io::Error),
+}
+
+#[derive(Debug, Fail)]
+pub enum TestDockerImageError {
+    #[fail(display = "Failed to run test command in image {}: {}", _0, _1)]
+    RunCommandError(String, String, #[cause] io::Error),
+
+    #[fail(
+        display = "Test command in image {} exited with code {} instead of {}: {}",
+        _0, _2, _3, _1
+    )]
+    UnexpectedExitCode(String, String, i32, i32),
+
+    #[fail(display = "Invalid output pattern in tests for image {}: {}", _0, _1)]
+    InvalidOutputPattern(String, String, #[cause] glob::PatternError),
+
+    #[fail(
+        display = "Output of test command in image {} has no line matching {}: {}",
+        _0, _2, _1
+    )]
+    MissingOutput(String, String, String),
+
+    #[fail(display = "Expected file not found in image {}: {}", _0, _1)]
+    MissingFile(String, String),
+
+    #[fail(display = "Failed to inspect image: {}", _0)]
+    InspectError(String, #[cause] io::Error),
+
+    #[fail(
+        display = "Image {} does not set environment variable {}={}",
+        _0, _1, _2
+    )]
+    UnexpectedEnv(String, String, String),
+
+    #[fail(display = "Image {} runs as user {:?} instead of {:?}", _0, _1, _2)]
+    UnexpectedUser(String, String, String),
 }
 
+#[derive(Debug, Fail)]
+#[fail(display = "Failed to compute fingerprint of image: {}", _0)]
+pub struct FingerprintError(String, #[cause] io::Error);
+
 impl DockerImage {
     pub fn new(
         base_dir: impl AsRef<Path>,
         image_tag: impl AsRef<str>,
         image_namespace: impl AsRef<str>,
+        variables: Option<&Variables>,
     ) -> Result<Self, NewDockerImageError> {
-        let (image_tag, image_name, image_namespace) =
-            Self::parse_image_tag(image_tag.as_ref(), image_namespace.as_ref())
-                .map_err(NewDockerImageError::InvalidTag)?;
-        let source_directory = base_dir.as_ref().join(image_namespace).join(image_name);
-        let dockerfile = Dockerfile::from_file(source_directory.join("dockerfile.yml"))
+        let reference = Self::parse_image_tag(image_tag.as_ref(), image_namespace.as_ref())
+            .map_err(NewDockerImageError::InvalidTag)?;
+        let unversioned_reference = reference.without_version();
+        let image_tag = unversioned_reference.to_string();
+        let image_directory = base_dir
+            .as_ref()
+            .join(image_namespace.as_ref())
+            .join(reference.name());
+        let mut definition = ImageDefinition::load(base_dir.as_ref(), &reference.repository())
             .map_err(|error| NewDockerImageError::DockerfileError(image_tag.clone(), error))?;
 
+        if let Some(variables) = variables {
+            definition
+                .evaluate_conditions(variables)
+                .map_err(|error| NewDockerImageError::ConditionError(image_tag.clone(), error))?;
+        }
+
+        let metadata = definition.metadata();
+        let source_directory = match metadata.and_then(|metadata| metadata.context()) {
+            Some(context) => image_directory.join(context),
+            None => image_directory.clone(),
+        };
+        let named_contexts = metadata
+            .into_iter()
+            .flat_map(|metadata| metadata.contexts())
+            .map(|(name, location)| {
+                let location = if location.contains("://") {
+                    location.to_owned()
+                } else {
+                    image_directory.join(location).display().to_string()
+                };
+
+                (name.to_owned(), location)
+            })
+            .collect();
+        let versions = metadata
+            .and_then(|metadata| metadata.version())
+            .into_iter()
+            .chain(metadata.into_iter().flat_map(|metadata| metadata.tags()))
+            .chain(reference.tag());
+        let mut tags = vec![image_tag.clone()];
+
+        for version in versions {
+            let versioned_tag = unversioned_reference.with_tag(version).to_string();
+
+            if version != "latest" && !tags.contains(&versioned_tag) {
+                tags.push(versioned_tag);
+            }
+        }
+
         Ok(DockerImage {
             tag: image_tag,
-            dockerfile,
+            tags,
+            definition,
             source_directory,
+            image_directory,
+            named_contexts,
         })
     }
 
-    fn parse_image_tag<'a, 'b>(
-        image_tag: &'a str,
-        image_namespace: &'b str,
-    ) -> Result<(String, String, &'b str), String> {
-        if let Some(position) = image_tag.find("/") {
-            if image_tag.starts_with(image_namespace) && position == image_namespace.len() {
-                let (_, image_name) = image_tag.split_at(position + 1);
+    fn parse_image_tag(image_tag: &str, image_namespace: &str) -> Result<ImageReference, String> {
+        let mut reference: ImageReference = image_tag.parse().map_err(|_| image_tag.to_owned())?;
+
+        match reference.namespace() {
+            Some(namespace) if namespace == image_namespace => {}
+            Some(_) => return Err(image_tag.to_owned()),
+            None => reference.set_namespace(image_namespace),
+        }
+
+        Ok(reference)
+    }
+
+    pub fn tag(&self) -> &str {
+        &self.tag
+    }
+
+    pub fn definition(&self) -> &ImageDefinition {
+        &self.definition
+    }
+
+    pub fn pin_base_images(&mut self, lockfile: &Lockfile) {
+        self.definition
+            .pin_base_images(|image| lockfile.pinned_image(image));
+    }
+
+    pub fn source_images(&self) -> impl Iterator<Item = &str> {
+        self.definition.from().chain(self.definition.copy_sources())
+    }
+
+    pub fn base_images(&self) -> impl Iterator<Item = &str> {
+        self.definition.from()
+    }
+
+    pub fn copy_sources(&self) -> impl Iterator<Item = &str> {
+        self.definition.copy_sources()
+    }
+
+    pub fn build_options(&self, options: &BuildOptions) -> BuildOptions {
+        let mut image_options = options.clone();
+        let settings = match self.definition.metadata() {
+            Some(metadata) => metadata.build_settings(),
+            None => return image_options,
+        };
+
+        image_options.no_cache |= settings.no_cache();
+        image_options.pull |= settings.pull();
+
+        if image_options.network.is_none() {
+            image_options.network = settings.network().map(str::to_owned);
+        }
+
+        if image_options.target.is_none() {
+            image_options.target = settings.target().map(str::to_owned);
+        }
+
+        if image_options.progress.is_none() {
+            image_options.progress = settings.progress().map(str::to_owned);
+        }
+
+        let image_labels = settings
+            .labels()
+            .filter(|(name, _)| !options.labels.iter().any(|(label, _)| label == name))
+            .map(|(name, value)| (name.to_owned(), value.to_owned()));
+
+        image_options.labels = image_labels.chain(options.labels.clone()).collect();
+        image_options
+    }
+
+    pub fn fingerprint<'a>(
+        &self,
+        options: &BuildOptions,
+        parent_fingerprints: impl IntoIterator<Item = &'a str>,
+    ) -> Result<String, FingerprintError> {
+        let options = self.build_options(options);
+        let mut fingerprint = Fingerprint::new();
+
+        fingerprint.add("dockerfile", format!("{:#}", self.definition));
+
+        if let Some(metadata) = self.definition.metadata() {
+            fingerprint.add("tests", format!("{:?}", metadata.tests()));
+        }
+
+        for tag in &self.tags {
+            fingerprint.add("tag", tag);
+        }
+
+        if let Some(dockerignore) = self
+            .definition
+            .dockerignore(&self.source_directory, &self.image_directory)
+            .map_err(|error| FingerprintError(self.tag.clone(), error))?
+        {
+            fingerprint.add("dockerignore", dockerignore);
+        }
+
+        if let Some(platform) = &options.platform {
+            fingerprint.add("platform", platform);
+        }
+
+        for (name, value) in &options.build_args {
+            fingerprint.add("build-arg", format!("{}={}", name, value));
+        }
+
+        if let Some(target) = &options.target {
+            fingerprint.add("target", target);
+        }
+
+        for (name, value) in &options.labels {
+            fingerprint.add("label", format!("{}={}", name, value));
+        }
+
+        for source in self.definition.context_sources() {
+            fingerprint
+                .add_context_files(&self.source_directory, source)
+                .map_err(|error| FingerprintError(self.tag.clone(), error))?;
+        }
+
+        for (name, location) in &self.named_contexts {
+            fingerprint.add("build-context", format!("{}={}", name, location));
+        }
+
+        for (context, source) in self.definition.named_context_sources() {
+            let location = self
+                .named_contexts
+                .iter()
+                .find(|(name, _)| name == context)
+                .map(|(_, location)| location);
 
-                Ok((image_tag.to_owned(), image_name.to_owned(), image_namespace))
-            } else {
-                Err(image_tag.to_owned())
+            if let Some(location) = location {
+                if !location.contains("://") {
+                    fingerprint
+                        .add_context_files(Path::new(location), source)
+                        .map_err(|error| FingerprintError(self.tag.clone(), error))?;
+                }
             }
-        } else {
-            let image_name = image_tag.to_owned();
-            let mut image_tag = image_namespace.to_owned();
+        }
 
-            image_tag.push('/');
-            image_tag.push_str(&image_name);
+        for parent_fingerprint in parent_fingerprints {
+            fingerprint.add("parent", parent_fingerprint);
+        }
+
+        Ok(fingerprint.finish())
+    }
 
-            Ok((image_tag, image_name, image_namespace))
+    pub fn is_up_to_date(&self, fingerprint: &str) -> bool {
+        match docker::inspect().image_label(&self.tag, FINGERPRINT_LABEL) {
+            Ok(Some(existing_fingerprint)) => existing_fingerprint == fingerprint,
+            _ => false,
         }
     }
 
-    pub fn source_images(&self) -> impl Iterator<Item = &str> {
-        self.dockerfile.from()
+    pub fn build(
+        &self,
+        options: &BuildOptions,
+        fingerprint: Option<&str>,
+    ) -> Result<(), BuildDockerImageError> {
+        let tags: Vec<&str> = self.tags.iter().map(String::as_str).collect();
+
+        self.build_with_tags(options, fingerprint, &tags)
+    }
+
+    pub fn build_as(
+        &self,
+        options: &BuildOptions,
+        fingerprint: Option<&str>,
+        tag: &str,
+    ) -> Result<(), BuildDockerImageError> {
+        self.build_with_tags(options, fingerprint, &[tag])
+    }
+
+    fn build_with_tags(
+        &self,
+        options: &BuildOptions,
+        fingerprint: Option<&str>,
+        tags: &[&str],
+    ) -> Result<(), BuildDockerImageError> {
+        let dockerfile = self.definition.to_string();
+        let dockerfile_dir = self.write_build_files(&dockerfile)?;
+        let options = self.build_options(options);
+        let result = self.run_build(
+            &options,
+            &dockerfile,
+            dockerfile_dir.path(),
+            tags,
+            fingerprint,
+        );
+        let keep_dir = match (&options.keep_dockerfiles, &result) {
+            (Some(keep_dir), _) => keep_dir,
+            (None, Err(_)) => match &options.log_dir {
+                Some(log_dir) => log_dir,
+                None => return result,
+            },
+            (None, Ok(())) => return result,
+        };
+        let kept_dockerfile = self.keep_build_files(dockerfile_dir.path(), keep_dir);
+
+        match (result, kept_dockerfile) {
+            (Ok(()), Ok(_)) => Ok(()),
+            (Ok(()), Err(error)) => {
+                eprintln!(
+                    "Failed to keep Dockerfile of image {} in {}: {}",
+                    self.tag,
+                    keep_dir.display(),
+                    error
+                );
+                Ok(())
+            }
+            (Err(build_error), Ok(kept_dockerfile)) => {
+                if let Some(log_dir) = &options.log_dir {
+                    let _ = OpenOptions::new()
+                        .append(true)
+                        .open(self.log_path(log_dir))
+                        .and_then(|mut log_file| {
+                            writeln!(
+                                log_file,
+                                "Dockerfile kept at: {}",
+                                kept_dockerfile.display()
+                            )
+                        });
+                }
+
+                Err(build_error)
+            }
+            (Err(build_error), Err(error)) => {
+                eprintln!(
+                    "Failed to keep Dockerfile of failed build of image {}: {}",
+                    self.tag, error
+                );
+                Err(build_error)
+            }
+        }
+    }
+
+    fn run_build(
+        &self,
+        options: &BuildOptions,
+        dockerfile: &str,
+        dockerfile_dir: &Path,
+        tags: &[&str],
+        fingerprint: Option<&str>,
+    ) -> Result<(), BuildDockerImageError> {
+        let dockerfile_path = dockerfile_dir.join("Dockerfile");
+        let command = self.build_command(
+            options,
+            dockerfile_path.to_string_lossy(),
+            tags.iter().cloned(),
+            fingerprint,
+        );
+        let log_path = match &options.log_dir {
+            Some(log_dir) => self.log_path(log_dir),
+            None => {
+                return command.run().map_err(|error| {
+                    BuildDockerImageError::DockerCommandError(self.tag.clone(), error)
+                });
+            }
+        };
+        let mut log_file = File::create(&log_path)
+            .map_err(|error| BuildDockerImageError::CreateLogError(self.tag.clone(), error))?;
+
+        command
+            .run_with_output_handler(|line| {
+                let _ = writeln!(log_file, "{}", line);
+
+                if options.prefix_output {
+                    println!("[{}] {}", self.tag, line);
+                } else {
+                    println!("{}", line);
+                }
+            })
+            .map_err(|error| {
+                let location = fs::read_to_string(&log_path).ok().and_then(|log| {
+                    let instruction = build_log::failed_instruction(&log, dockerfile)?;
+
+                    self.definition.source_location(instruction.failed_line())
+                });
+
+                match location {
+                    Some(location) => BuildDockerImageError::FailedInstructionError(
+                        self.tag.clone(),
+                        location.to_string(),
+                        log_path.display().to_string(),
+                        error,
+                    ),
+                    None => BuildDockerImageError::LoggedDockerCommandError(
+                        self.tag.clone(),
+                        log_path.display().to_string(),
+                        error,
+                    ),
+                }
+            })
+    }
+
+    pub fn debug_failed_build(&self, options: &BuildOptions) -> Result<(), DebugDockerImageError> {
+        let log_path = match &options.log_dir {
+            Some(log_dir) => self.log_path(log_dir),
+            None => return Err(DebugDockerImageError::MissingBuildLog(self.tag.clone())),
+        };
+        let log = fs::read_to_string(&log_path)
+            .map_err(|error| DebugDockerImageError::ReadLogError(self.tag.clone(), error))?;
+        let dockerfile = self.definition.to_string();
+        let instruction = build_log::failed_instruction(&log, &dockerfile)
+            .ok_or_else(|| DebugDockerImageError::UnknownFailedInstruction(self.tag.clone()))?;
+        let partial_dockerfile: String = dockerfile
+            .lines()
+            .take(instruction.line() - 1)
+            .map(|line| format!("{}\n", line))
+            .collect();
+
+        if instruction.text().to_uppercase().starts_with("FROM ") {
+            return Err(DebugDockerImageError::NothingToDebug(
+                self.tag.clone(),
+                instruction.text().to_owned(),
+            ));
+        }
+
+        let debug_tag = format!("{}:dkr-debug", self.tag);
+        let dockerfile_dir = self
+            .write_build_files(&partial_dockerfile)
+            .map_err(DebugDockerImageError::BuildError)?;
+        let dockerfile_path = dockerfile_dir.path().join("Dockerfile");
+        let mut debug_options = self.build_options(options);
+
+        debug_options.no_cache = false;
+        debug_options.pull = false;
+        debug_options.target = None;
+
+        println!("Rebuilding image {} up to its failed instruction", self.tag);
+
+        self.build_command(
+            &debug_options,
+            dockerfile_path.to_string_lossy(),
+            iter::once(debug_tag.as_str()),
+            None,
+        )
+        .run()
+        .map_err(|error| {
+            DebugDockerImageError::BuildError(BuildDockerImageError::DockerCommandError(
+                self.tag.clone(),
+                error,
+            ))
+        })?;
+
+        println!();
+        println!(
+            "Image {} failed at line {} of its Dockerfile:",
+            self.tag,
+            instruction.line()
+        );
+        println!("    {}", instruction.text());
+        println!("Starting a shell in the image built up to that instruction");
+
+        let mut shell = docker::run(debug_tag.as_str());
+
+        shell.temporary().interactive();
+
+        let result = shell.run_shell();
+
+        if let Err(error) = docker::rmi().remove(iter::once(debug_tag.as_str())) {
+            eprintln!("Failed to remove debugging image {}: {}", debug_tag, error);
+        }
+
+        result
+            .map(|_| ())
+            .map_err(|error| DebugDockerImageError::ShellError(self.tag.clone(), error))
     }
 
-    pub fn build(&self) -> Result<(), BuildDockerImageError> {
-        let dockerfile = NamedTempFile::new().map_err(|error| {
+    fn write_build_files(&self, dockerfile: &str) -> Result<TempDir, BuildDockerImageError> {
+        let dockerfile_dir = TempDir::new().map_err(|error| {
             BuildDockerImageError::CreateDockerfileError(self.tag.clone(), error)
         })?;
 
-        write!(dockerfile.as_file(), "{}", self.dockerfile).map_err(|error| {
+        fs::write(dockerfile_dir.path().join("Dockerfile"), dockerfile).map_err(|error| {
             BuildDockerImageError::WriteDockerfileError(self.tag.clone(), error)
         })?;
 
-        cmd!(
-            "docker",
-            "build",
-            "-t",
-            &self.tag,
-            "-f",
-            dockerfile.path(),
-            &self.source_directory
+        let dockerignore = self
+            .definition
+            .dockerignore(&self.source_directory, &self.image_directory)
+            .and_then(|dockerignore| match dockerignore {
+                Some(contents) => fs::write(
+                    dockerfile_dir.path().join("Dockerfile.dockerignore"),
+                    contents,
+                ),
+                None => Ok(()),
+            });
+
+        dockerignore.map_err(|error| {
+            BuildDockerImageError::WriteDockerignoreError(self.tag.clone(), error)
+        })?;
+
+        Ok(dockerfile_dir)
+    }
+
+    fn keep_build_files(
+        &self,
+        dockerfile_dir: &Path,
+        keep_dir: &Path,
+    ) -> Result<PathBuf, io::Error> {
+        let kept_dockerfile = keep_dir.join(format!("{}.Dockerfile", self.file_stem()));
+        let dockerignore = dockerfile_dir.join("Dockerfile.dockerignore");
+
+        fs::copy(dockerfile_dir.join("Dockerfile"), &kept_dockerfile)?;
+
+        if dockerignore.exists() {
+            fs::copy(
+                dockerignore,
+                keep_dir.join(format!("{}.Dockerfile.dockerignore", self.file_stem())),
+            )?;
+        }
+
+        Ok(kept_dockerfile)
+    }
+
+    fn log_path(&self, log_dir: &Path) -> PathBuf {
+        log_dir.join(format!("{}.log", self.file_stem()))
+    }
+
+    fn file_stem(&self) -> String {
+        self.tag.replace(['/', ':'], "_")
+    }
+
+    pub fn dockerfile_hash(&self) -> String {
+        format!(
+            "{:x}",
+            Sha256::digest(format!("{:#}", self.definition).as_bytes())
         )
-        .run()
-        .map(|_| ())
-        .map_err(|error| BuildDockerImageError::DockerCommandError(self.tag.clone(), error))
+    }
+
+    pub fn id_and_size(&self) -> Result<(String, u64), io::Error> {
+        docker::inspect().image_id_and_size(&self.tag)
+    }
+
+    pub fn test_tag(&self, fingerprint: &str) -> Option<String> {
+        let metadata = self.definition.metadata()?;
+
+        if metadata.tests().is_empty() {
+            None
+        } else {
+            Some(format!("{}:dkr-test-{}", self.tag, fingerprint))
+        }
+    }
+
+    pub fn test(&self) -> Result<(), TestDockerImageError> {
+        self.test_image(&self.tag)
+    }
+
+    pub fn test_image(&self, image: &str) -> Result<(), TestDockerImageError> {
+        let tests = match self.definition.metadata() {
+            Some(metadata) => metadata.tests(),
+            None => return Ok(()),
+        };
+
+        for test in tests.commands() {
+            println!("Testing image {}: {}", self.tag, test.run());
+
+            let (exit_code, output) = self.run_test_command(image, test.run())?;
+
+            if exit_code != test.exit_code() {
+                return Err(TestDockerImageError::UnexpectedExitCode(
+                    self.tag.clone(),
+                    test.run().to_owned(),
+                    exit_code,
+                    test.exit_code(),
+                ));
+            }
+
+            for pattern in test.output() {
+                let glob_pattern = glob::Pattern::new(pattern).map_err(|error| {
+                    TestDockerImageError::InvalidOutputPattern(
+                        self.tag.clone(),
+                        pattern.to_owned(),
+                        error,
+                    )
+                })?;
+
+                if !output.lines().any(|line| glob_pattern.matches(line)) {
+                    return Err(TestDockerImageError::MissingOutput(
+                        self.tag.clone(),
+                        test.run().to_owned(),
+                        pattern.to_owned(),
+                    ));
+                }
+            }
+        }
+
+        for file in tests.files() {
+            println!("Testing image {}: file {} exists", self.tag, file);
+
+            let check = format!("test -e '{}'", file.replace('\'', "'\\''"));
+            let (exit_code, _) = self.run_test_command(image, &check)?;
+
+            if exit_code != 0 {
+                return Err(TestDockerImageError::MissingFile(
+                    self.tag.clone(),
+                    file.to_owned(),
+                ));
+            }
+        }
+
+        let mut env = tests.env().peekable();
+
+        if env.peek().is_some() {
+            let image_env = docker::inspect()
+                .image_env(image)
+                .map_err(|error| TestDockerImageError::InspectError(self.tag.clone(), error))?;
+
+            for (name, value) in env {
+                let variable = format!("{}={}", name, value);
+
+                if !image_env.contains(&variable) {
+                    return Err(TestDockerImageError::UnexpectedEnv(
+                        self.tag.clone(),
+                        name.to_owned(),
+                        value.to_owned(),
+                    ));
+                }
+            }
+        }
+
+        if let Some(user) = tests.user() {
+            let image_user = docker::inspect()
+                .image_user(image)
+                .map_err(|error| TestDockerImageError::InspectError(self.tag.clone(), error))?;
+
+            if image_user != user {
+                return Err(TestDockerImageError::UnexpectedUser(
+                    self.tag.clone(),
+                    image_user,
+                    user.to_owned(),
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn run_test_command(
+        &self,
+        image: &str,
+        command: &str,
+    ) -> Result<(i32, String), TestDockerImageError> {
+        let mut run = docker::run(image);
+
+        run.temporary();
+        run.shell_command_output(command).map_err(|error| {
+            TestDockerImageError::RunCommandError(self.tag.clone(), command.to_owned(), error)
+        })
+    }
+
+    pub fn tag_tested(&self, test_tag: &str) -> Result<(), io::Error> {
+        for tag in &self.tags {
+            docker::tag().tag(test_tag, tag)?;
+        }
+
+        self.remove_test_tag(test_tag)
+    }
+
+    pub fn remove_test_tag(&self, test_tag: &str) -> Result<(), io::Error> {
+        docker::rmi().remove(iter::once(test_tag))
+    }
+
+    pub fn push(&self, registry: Option<&str>) -> Result<(), PushDockerImageError> {
+        for (tag, remote_tag) in self.remote_tags(registry) {
+            if remote_tag != *tag {
+                docker::tag().tag(tag, &remote_tag).map_err(|error| {
+                    PushDockerImageError::TagCommandError(tag.clone(), remote_tag.clone(), error)
+                })?;
+            }
+
+            docker::push()
+                .push(&remote_tag)
+                .map_err(|error| PushDockerImageError::PushCommandError(remote_tag, error))?;
+        }
+
+        Ok(())
+    }
+
+    pub fn push_command_lines(&self, registry: Option<&str>) -> Vec<String> {
+        let mut command_lines = Vec::new();
+
+        for (tag, remote_tag) in self.remote_tags(registry) {
+            if remote_tag != *tag {
+                command_lines.push(format!("docker tag {} {}", tag, remote_tag));
+            }
+
+            command_lines.push(format!("docker push {}", remote_tag));
+        }
+
+        command_lines
+    }
+
+    fn remote_tags<'a>(
+        &'a self,
+        registry: Option<&'a str>,
+    ) -> impl Iterator<Item = (&'a String, String)> {
+        self.tags.iter().map(move |tag| {
+            let has_registry = tag
+                .parse::<ImageReference>()
+                .map(|reference| reference.registry().is_some())
+                .unwrap_or(false);
+
+            match registry {
+                Some(registry) if !has_registry => {
+                    (tag, format!("{}/{}", registry.trim_end_matches('/'), tag))
+                }
+                _ => (tag, tag.clone()),
+            }
+        })
+    }
+
+    pub fn build_command_line(&self, options: &BuildOptions, fingerprint: Option<&str>) -> String {
+        self.build_command(
+            &self.build_options(options),
+            "<generated Dockerfile>",
+            self.tags.iter().map(String::as_str),
+            fingerprint,
+        )
+        .to_string()
+    }
+
+    fn build_command<'a>(
+        &'a self,
+        options: &'a BuildOptions,
+        dockerfile: impl Into<Cow<'a, str>>,
+        tags: impl IntoIterator<Item = &'a str>,
+        fingerprint: Option<&str>,
+    ) -> DockerBuild<'a> {
+        let mut command = docker::build(self.source_directory.to_string_lossy());
+
+        for tag in tags {
+            command.tag(tag);
+        }
+
+        command.dockerfile(dockerfile);
+
+        if let Some(platform) = &options.platform {
+            command.platform(platform.as_str());
+        }
+
+        for (name, value) in &options.build_args {
+            command.build_arg(name, value);
+        }
+
+        if options.no_cache {
+            command.no_cache();
+        }
+
+        if options.pull {
+            command.pull();
+        }
+
+        if let Some(network) = &options.network {
+            command.network(network.as_str());
+        }
+
+        if let Some(target) = &options.target {
+            command.target(target.as_str());
+        }
+
+        if let Some(progress) = &options.progress {
+            command.progress(progress.as_str());
+        }
+
+        for (name, value) in &options.labels {
+            command.label(name, value);
+        }
+
+        for (name, location) in &self.named_contexts {
+            command.build_context(name, location);
+        }
+
+        if let Some(fingerprint) = fingerprint {
+            command.label(FINGERPRINT_LABEL, fingerprint);
+        }
+
+        command
     }
 }
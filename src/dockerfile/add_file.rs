@@ -7,6 +7,12 @@ pub struct AddFile {
     to: String,
 }
 
+impl AddFile {
+    pub fn source(&self) -> &str {
+        &self.from
+    }
+}
+
 impl Display for AddFile {
     fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
         writeln!(formatter, "ADD {} {}", self.from, self.to)
@@ -0,0 +1,44 @@
+use serde::Deserialize;
+use std::collections::BTreeMap;
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct BuildSettings {
+    #[serde(default)]
+    no_cache: bool,
+    #[serde(default)]
+    pull: bool,
+    network: Option<String>,
+    target: Option<String>,
+    progress: Option<String>,
+    #[serde(default)]
+    labels: BTreeMap<String, String>,
+}
+
+impl BuildSettings {
+    pub fn no_cache(&self) -> bool {
+        self.no_cache
+    }
+
+    pub fn pull(&self) -> bool {
+        self.pull
+    }
+
+    pub fn network(&self) -> Option<&str> {
+        self.network.as_deref()
+    }
+
+    pub fn target(&self) -> Option<&str> {
+        self.target.as_deref()
+    }
+
+    pub fn progress(&self) -> Option<&str> {
+        self.progress.as_deref()
+    }
+
+    pub fn labels(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.labels
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+    }
+}
@@ -0,0 +1,432 @@
+use super::Variables;
+use failure::Fail;
+use serde::{de, Deserialize, Deserializer};
+use std::{
+    fmt::{self, Display, Formatter},
+    iter::Peekable,
+    str::{Chars, FromStr},
+    vec,
+};
+
+#[derive(Debug)]
+pub enum Condition {
+    Or(Box<Condition>, Box<Condition>),
+    And(Box<Condition>, Box<Condition>),
+    Not(Box<Condition>),
+    Equals(Operand, Operand),
+    NotEquals(Operand, Operand),
+    In(Operand, Vec<Operand>),
+    Value(Operand),
+}
+
+#[derive(Debug)]
+pub enum Operand {
+    Variable(String),
+    Literal(String),
+}
+
+#[derive(Debug, Fail)]
+pub enum ParseConditionError {
+    #[fail(display = "Unexpected character in condition: {}", _0)]
+    UnexpectedCharacter(char),
+
+    #[fail(display = "Unterminated string in condition")]
+    UnterminatedString,
+
+    #[fail(display = "Unexpected token in condition: {}", _0)]
+    UnexpectedToken(String),
+
+    #[fail(display = "Unexpected end of condition")]
+    UnexpectedEnd,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Word(String),
+    String(String),
+    OpenParenthesis,
+    CloseParenthesis,
+    OpenBracket,
+    CloseBracket,
+    Comma,
+    Equals,
+    NotEquals,
+    Not,
+    And,
+    Or,
+    In,
+}
+
+impl Condition {
+    pub fn evaluate(&self, variables: &Variables) -> bool {
+        match self {
+            Condition::Or(left, right) => left.evaluate(variables) || right.evaluate(variables),
+            Condition::And(left, right) => left.evaluate(variables) && right.evaluate(variables),
+            Condition::Not(condition) => !condition.evaluate(variables),
+            Condition::Equals(left, right) => left.value(variables) == right.value(variables),
+            Condition::NotEquals(left, right) => left.value(variables) != right.value(variables),
+            Condition::In(operand, list) => {
+                let value = operand.value(variables);
+
+                list.iter().any(|element| element.value(variables) == value)
+            }
+            Condition::Value(operand) => {
+                !matches!(operand.value(variables), "" | "0" | "false" | "no" | "off")
+            }
+        }
+    }
+}
+
+impl Operand {
+    fn value<'a>(&'a self, variables: &'a Variables) -> &'a str {
+        match self {
+            Operand::Variable(name) => variables.get(name).map(String::as_str).unwrap_or(""),
+            Operand::Literal(value) => value,
+        }
+    }
+}
+
+impl FromStr for Condition {
+    type Err = ParseConditionError;
+
+    fn from_str(source: &str) -> Result<Self, Self::Err> {
+        let tokens = tokenize(source)?;
+        let mut parser = Parser {
+            tokens: tokens.into_iter().peekable(),
+        };
+        let condition = parser.parse_or()?;
+
+        match parser.tokens.next() {
+            Some(token) => Err(ParseConditionError::UnexpectedToken(token.to_string())),
+            None => Ok(condition),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Condition {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let source = String::deserialize(deserializer)?;
+
+        source.parse().map_err(de::Error::custom)
+    }
+}
+
+struct Parser {
+    tokens: Peekable<vec::IntoIter<Token>>,
+}
+
+impl Parser {
+    fn parse_or(&mut self) -> Result<Condition, ParseConditionError> {
+        let mut condition = self.parse_and()?;
+
+        while self.tokens.peek() == Some(&Token::Or) {
+            self.tokens.next();
+            condition = Condition::Or(Box::new(condition), Box::new(self.parse_and()?));
+        }
+
+        Ok(condition)
+    }
+
+    fn parse_and(&mut self) -> Result<Condition, ParseConditionError> {
+        let mut condition = self.parse_not()?;
+
+        while self.tokens.peek() == Some(&Token::And) {
+            self.tokens.next();
+            condition = Condition::And(Box::new(condition), Box::new(self.parse_not()?));
+        }
+
+        Ok(condition)
+    }
+
+    fn parse_not(&mut self) -> Result<Condition, ParseConditionError> {
+        if self.tokens.peek() == Some(&Token::Not) {
+            self.tokens.next();
+
+            Ok(Condition::Not(Box::new(self.parse_not()?)))
+        } else {
+            self.parse_comparison()
+        }
+    }
+
+    fn parse_comparison(&mut self) -> Result<Condition, ParseConditionError> {
+        if self.tokens.peek() == Some(&Token::OpenParenthesis) {
+            self.tokens.next();
+
+            let condition = self.parse_or()?;
+
+            self.expect(Token::CloseParenthesis)?;
+
+            return Ok(condition);
+        }
+
+        let left = self.parse_operand()?;
+
+        match self.tokens.peek() {
+            Some(Token::Equals) => {
+                self.tokens.next();
+                Ok(Condition::Equals(left, self.parse_operand()?))
+            }
+            Some(Token::NotEquals) => {
+                self.tokens.next();
+                Ok(Condition::NotEquals(left, self.parse_operand()?))
+            }
+            Some(Token::In) => {
+                self.tokens.next();
+                Ok(Condition::In(left, self.parse_list()?))
+            }
+            Some(Token::Not) => {
+                self.tokens.next();
+                self.expect(Token::In)?;
+                Ok(Condition::Not(Box::new(Condition::In(
+                    left,
+                    self.parse_list()?,
+                ))))
+            }
+            _ => Ok(Condition::Value(left)),
+        }
+    }
+
+    fn parse_list(&mut self) -> Result<Vec<Operand>, ParseConditionError> {
+        let mut list = Vec::new();
+
+        self.expect(Token::OpenBracket)?;
+
+        if self.tokens.peek() == Some(&Token::CloseBracket) {
+            self.tokens.next();
+            return Ok(list);
+        }
+
+        loop {
+            list.push(self.parse_operand()?);
+
+            match self.tokens.next() {
+                Some(Token::Comma) => continue,
+                Some(Token::CloseBracket) => return Ok(list),
+                Some(token) => return Err(ParseConditionError::UnexpectedToken(token.to_string())),
+                None => return Err(ParseConditionError::UnexpectedEnd),
+            }
+        }
+    }
+
+    fn parse_operand(&mut self) -> Result<Operand, ParseConditionError> {
+        match self.tokens.next() {
+            Some(Token::String(value)) => Ok(Operand::Literal(value)),
+            Some(Token::Word(word)) => {
+                if word == "true"
+                    || word == "false"
+                    || word.starts_with(|c: char| c.is_ascii_digit())
+                {
+                    Ok(Operand::Literal(word))
+                } else {
+                    Ok(Operand::Variable(word))
+                }
+            }
+            Some(token) => Err(ParseConditionError::UnexpectedToken(token.to_string())),
+            None => Err(ParseConditionError::UnexpectedEnd),
+        }
+    }
+
+    fn expect(&mut self, expected: Token) -> Result<(), ParseConditionError> {
+        match self.tokens.next() {
+            Some(ref token) if *token == expected => Ok(()),
+            Some(token) => Err(ParseConditionError::UnexpectedToken(token.to_string())),
+            None => Err(ParseConditionError::UnexpectedEnd),
+        }
+    }
+}
+
+fn tokenize(source: &str) -> Result<Vec<Token>, ParseConditionError> {
+    let mut tokens = Vec::new();
+    let mut characters = source.chars().peekable();
+
+    while let Some(&character) = characters.peek() {
+        let token = match character {
+            ' ' | '\t' | '\n' => {
+                characters.next();
+                continue;
+            }
+            '(' => Token::OpenParenthesis,
+            ')' => Token::CloseParenthesis,
+            '[' => Token::OpenBracket,
+            ']' => Token::CloseBracket,
+            ',' => Token::Comma,
+            '\'' | '"' => {
+                characters.next();
+                tokens.push(Token::String(tokenize_string(&mut characters, character)?));
+                continue;
+            }
+            '=' | '!' | '&' | '|' => {
+                characters.next();
+
+                let token = match (character, characters.peek()) {
+                    ('=', Some('=')) => Token::Equals,
+                    ('!', Some('=')) => Token::NotEquals,
+                    ('&', Some('&')) => Token::And,
+                    ('|', Some('|')) => Token::Or,
+                    ('!', _) => {
+                        tokens.push(Token::Not);
+                        continue;
+                    }
+                    _ => return Err(ParseConditionError::UnexpectedCharacter(character)),
+                };
+
+                characters.next();
+                tokens.push(token);
+                continue;
+            }
+            character if is_word_character(character) => {
+                let mut word = String::new();
+
+                while let Some(&character) = characters.peek() {
+                    if !is_word_character(character) {
+                        break;
+                    }
+
+                    word.push(character);
+                    characters.next();
+                }
+
+                tokens.push(match word.as_str() {
+                    "and" => Token::And,
+                    "or" => Token::Or,
+                    "not" => Token::Not,
+                    "in" => Token::In,
+                    _ => Token::Word(word),
+                });
+                continue;
+            }
+            character => return Err(ParseConditionError::UnexpectedCharacter(character)),
+        };
+
+        characters.next();
+        tokens.push(token);
+    }
+
+    Ok(tokens)
+}
+
+fn tokenize_string(
+    characters: &mut Peekable<Chars>,
+    delimiter: char,
+) -> Result<String, ParseConditionError> {
+    let mut string = String::new();
+
+    while let Some(character) = characters.next() {
+        match character {
+            '\\' => match characters.next() {
+                Some(escaped) => string.push(escaped),
+                None => break,
+            },
+            character if character == delimiter => return Ok(string),
+            character => string.push(character),
+        }
+    }
+
+    Err(ParseConditionError::UnterminatedString)
+}
+
+fn is_word_character(character: char) -> bool {
+    character.is_alphanumeric() || character == '_' || character == '-' || character == '.'
+}
+
+impl Display for Token {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        match self {
+            Token::Word(word) => write!(formatter, "{}", word),
+            Token::String(string) => write!(formatter, "{:?}", string),
+            Token::OpenParenthesis => write!(formatter, "("),
+            Token::CloseParenthesis => write!(formatter, ")"),
+            Token::OpenBracket => write!(formatter, "["),
+            Token::CloseBracket => write!(formatter, "]"),
+            Token::Comma => write!(formatter, ","),
+            Token::Equals => write!(formatter, "=="),
+            Token::NotEquals => write!(formatter, "!="),
+            Token::Not => write!(formatter, "not"),
+            Token::And => write!(formatter, "and"),
+            Token::Or => write!(formatter, "or"),
+            Token::In => write!(formatter, "in"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn evaluate(source: &str, variables: &[(&str, &str)]) -> bool {
+        let condition: Condition = source.parse().unwrap();
+        let variables: Variables = variables
+            .iter()
+            .map(|&(name, value)| (name.to_owned(), value.to_owned()))
+            .collect();
+
+        condition.evaluate(&variables)
+    }
+
+    #[test]
+    fn and_binds_tighter_than_or() {
+        assert!(evaluate("a || b && c", &[("a", "1")]));
+        assert!(!evaluate("a && b || c", &[("a", "1")]));
+        assert!(evaluate("a and b or c", &[("c", "1")]));
+    }
+
+    #[test]
+    fn parentheses_override_precedence() {
+        assert!(!evaluate("(a || b) && c", &[("a", "1")]));
+        assert!(evaluate("(a || b) && c", &[("b", "1"), ("c", "1")]));
+        assert!(evaluate("!(a && b)", &[("a", "1")]));
+    }
+
+    #[test]
+    fn not_negates_the_following_term() {
+        assert!(evaluate("!a", &[]));
+        assert!(!evaluate("not a", &[("a", "yes")]));
+        assert!(evaluate("!!a", &[("a", "yes")]));
+        assert!(evaluate("!a && b", &[("b", "1")]));
+        assert!(evaluate(
+            "os not in ['windows', 'macos']",
+            &[("os", "linux")]
+        ));
+    }
+
+    #[test]
+    fn compares_strings() {
+        assert!(evaluate("os == 'linux'", &[("os", "linux")]));
+        assert!(evaluate("os != \"linux\"", &[("os", "windows")]));
+        assert!(evaluate("os in ['linux', 'freebsd']", &[("os", "freebsd")]));
+        assert!(evaluate("version == 3", &[("version", "3")]));
+        assert!(evaluate("name == 'it\\'s'", &[("name", "it's")]));
+    }
+
+    #[test]
+    fn undefined_variables_are_empty() {
+        assert!(!evaluate("missing", &[]));
+        assert!(evaluate("missing == ''", &[]));
+        assert!(evaluate("missing != 'value'", &[]));
+        assert!(!evaluate("flag", &[("flag", "off")]));
+    }
+
+    #[test]
+    fn rejects_malformed_conditions() {
+        for source in &[
+            "",
+            "a &&",
+            "(a || b",
+            "a b",
+            "os in 'linux'",
+            "os in ['linux'",
+            "a & b",
+            "a == 'unterminated",
+            "a ~ b",
+        ] {
+            assert!(
+                source.parse::<Condition>().is_err(),
+                "{:?} should not parse",
+                source
+            );
+        }
+    }
+}
@@ -0,0 +1,230 @@
+use super::{
+    condition::Condition, single_or_multiple_items_visitor::SingleOrMultipleItemsVisitor,
+    source_location::SourceLocation, Variables,
+};
+use serde::{
+    de::{self, DeserializeSeed, MapAccess, SeqAccess, Visitor},
+    Deserialize, Deserializer,
+};
+use std::fmt::{self, Formatter};
+
+#[derive(Debug)]
+pub struct ConditionalItems {
+    entries: Vec<ConditionalEntry>,
+}
+
+#[derive(Debug)]
+struct ConditionalEntry {
+    items: Vec<String>,
+    when: Option<Condition>,
+    locations: Vec<SourceLocation>,
+}
+
+impl ConditionalItems {
+    pub fn deserialize<'de, D>(deserializer: D, items_key: &'static str) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Ok(ConditionalItems {
+            entries: deserializer.deserialize_any(ConditionalItemsVisitor { items_key })?,
+        })
+    }
+
+    pub fn evaluate_conditions(&mut self, variables: &Variables) {
+        self.entries.retain(|entry| match &entry.when {
+            Some(condition) => condition.evaluate(variables),
+            None => true,
+        });
+
+        for entry in &mut self.entries {
+            entry.when = None;
+        }
+    }
+
+    pub fn extend(&mut self, other: ConditionalItems) {
+        self.entries.extend(other.entries);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.iter().all(|entry| entry.items.is_empty())
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &str> {
+        self.entries
+            .iter()
+            .flat_map(|entry| entry.items.iter().map(String::as_str))
+    }
+
+    pub fn iter_with_locations(&self) -> impl Iterator<Item = (&str, Option<&SourceLocation>)> {
+        self.entries.iter().flat_map(|entry| {
+            entry
+                .items
+                .iter()
+                .enumerate()
+                .map(move |(index, item)| (item.as_str(), entry.locations.get(index)))
+        })
+    }
+
+    pub fn set_item_lines(&mut self, file: &str, document: usize, key: &str, lines: &[Vec<usize>]) {
+        for (entry, lines) in self.entries.iter_mut().zip(lines) {
+            if lines.len() == entry.items.len() {
+                entry.locations = lines
+                    .iter()
+                    .map(|&line| SourceLocation::new(file, document, key, line))
+                    .collect();
+            }
+        }
+    }
+
+    pub fn set_source_file(&mut self, file: &str) {
+        for location in self
+            .entries
+            .iter_mut()
+            .flat_map(|entry| entry.locations.iter_mut())
+        {
+            location.set_file(file);
+        }
+    }
+}
+
+struct ConditionalItemsVisitor {
+    items_key: &'static str,
+}
+
+impl<'de> Visitor<'de> for ConditionalItemsVisitor {
+    type Value = Vec<ConditionalEntry>;
+
+    fn expecting(&self, formatter: &mut Formatter) -> fmt::Result {
+        write!(
+            formatter,
+            "a string, a conditional map or a sequence of strings or conditional maps"
+        )
+    }
+
+    fn visit_str<E>(self, value: &str) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        Ok(vec![ConditionalEntry::unconditional(value)])
+    }
+
+    fn visit_seq<A>(self, mut sequence: A) -> Result<Self::Value, A::Error>
+    where
+        A: SeqAccess<'de>,
+    {
+        let mut entries = if let Some(size) = sequence.size_hint() {
+            Vec::with_capacity(size)
+        } else {
+            Vec::new()
+        };
+
+        while let Some(entry) = sequence.next_element_seed(ConditionalEntrySeed {
+            items_key: self.items_key,
+        })? {
+            entries.push(entry)
+        }
+
+        Ok(entries)
+    }
+
+    fn visit_map<A>(self, map: A) -> Result<Self::Value, A::Error>
+    where
+        A: MapAccess<'de>,
+    {
+        let entry_visitor = ConditionalEntryVisitor {
+            items_key: self.items_key,
+        };
+
+        Ok(vec![entry_visitor.visit_map(map)?])
+    }
+}
+
+impl ConditionalEntry {
+    fn unconditional(item: &str) -> Self {
+        ConditionalEntry {
+            items: vec![item.to_owned()],
+            when: None,
+            locations: Vec::new(),
+        }
+    }
+}
+
+struct ConditionalEntrySeed {
+    items_key: &'static str,
+}
+
+impl<'de> DeserializeSeed<'de> for ConditionalEntrySeed {
+    type Value = ConditionalEntry;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_any(ConditionalEntryVisitor {
+            items_key: self.items_key,
+        })
+    }
+}
+
+struct ConditionalEntryVisitor {
+    items_key: &'static str,
+}
+
+impl<'de> Visitor<'de> for ConditionalEntryVisitor {
+    type Value = ConditionalEntry;
+
+    fn expecting(&self, formatter: &mut Formatter) -> fmt::Result {
+        write!(
+            formatter,
+            "a string or a map with `when` and `{}` keys",
+            self.items_key
+        )
+    }
+
+    fn visit_str<E>(self, value: &str) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        Ok(ConditionalEntry::unconditional(value))
+    }
+
+    fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+    where
+        A: MapAccess<'de>,
+    {
+        let mut items = None;
+        let mut when = None;
+
+        while let Some(key) = map.next_key::<String>()? {
+            if key == "when" {
+                when = Some(map.next_value()?);
+            } else if key == self.items_key {
+                items = Some(map.next_value::<Items>()?.0);
+            } else {
+                return Err(de::Error::custom(format!(
+                    "unknown field `{}`, expected `when` or `{}`",
+                    key, self.items_key
+                )));
+            }
+        }
+
+        Ok(ConditionalEntry {
+            items: items.ok_or_else(|| de::Error::missing_field(self.items_key))?,
+            when,
+            locations: Vec::new(),
+        })
+    }
+}
+
+struct Items(Vec<String>);
+
+impl<'de> Deserialize<'de> for Items {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Ok(Items(
+            deserializer.deserialize_any(SingleOrMultipleItemsVisitor)?,
+        ))
+    }
+}
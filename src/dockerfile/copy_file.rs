@@ -6,15 +6,41 @@ pub struct CopyFile {
     from: String,
     to: String,
     stage: Option<usize>,
+    context: Option<String>,
+}
+
+impl CopyFile {
+    pub fn source(&self) -> &str {
+        &self.from
+    }
+
+    pub fn stage(&self) -> Option<usize> {
+        self.stage
+    }
+
+    pub fn context(&self) -> Option<&str> {
+        self.context.as_deref()
+    }
+
+    pub fn set_stage(&mut self, stage: usize) {
+        self.stage = Some(stage);
+    }
+
+    pub fn pin_context(&mut self, pinned_image: impl Fn(&str) -> Option<String>) {
+        if let Some(pinned) = self.context.as_deref().and_then(pinned_image) {
+            self.context = Some(pinned);
+        }
+    }
 }
 
 impl Display for CopyFile {
     fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
-        let stage = self
-            .stage
-            .map(|stage| format!("--from={} ", stage))
-            .unwrap_or_else(|| String::new());
+        let source = match (self.stage, &self.context) {
+            (Some(stage), _) => format!("--from={} ", stage),
+            (None, Some(context)) => format!("--from={} ", context),
+            (None, None) => String::new(),
+        };
 
-        writeln!(formatter, "COPY {}{} {}", stage, self.from, self.to)
+        writeln!(formatter, "COPY {}{} {}", source, self.from, self.to)
     }
 }
@@ -0,0 +1,65 @@
+use serde::Deserialize;
+use std::collections::BTreeMap;
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct ImageTests {
+    #[serde(default)]
+    commands: Vec<CommandTest>,
+    #[serde(default)]
+    files: Vec<String>,
+    #[serde(default)]
+    env: BTreeMap<String, String>,
+    user: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct CommandTest {
+    run: String,
+    #[serde(default)]
+    exit_code: i32,
+    #[serde(default)]
+    output: Vec<String>,
+}
+
+impl ImageTests {
+    pub fn commands(&self) -> impl Iterator<Item = &CommandTest> {
+        self.commands.iter()
+    }
+
+    pub fn files(&self) -> impl Iterator<Item = &str> {
+        self.files.iter().map(String::as_str)
+    }
+
+    pub fn env(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.env
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+    }
+
+    pub fn user(&self) -> Option<&str> {
+        self.user.as_deref()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.commands.is_empty()
+            && self.files.is_empty()
+            && self.env.is_empty()
+            && self.user.is_none()
+    }
+}
+
+impl CommandTest {
+    pub fn run(&self) -> &str {
+        &self.run
+    }
+
+    pub fn exit_code(&self) -> i32 {
+        self.exit_code
+    }
+
+    pub fn output(&self) -> impl Iterator<Item = &str> {
+        self.output.iter().map(String::as_str)
+    }
+}
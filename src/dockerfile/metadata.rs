@@ -0,0 +1,63 @@
+use super::{build_settings::BuildSettings, image_tests::ImageTests};
+use serde::Deserialize;
+use std::{collections::BTreeMap, mem};
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Metadata {
+    extends: Option<String>,
+    version: Option<String>,
+    #[serde(default)]
+    tags: Vec<String>,
+    #[serde(default)]
+    ignore: Vec<String>,
+    context: Option<String>,
+    #[serde(default)]
+    contexts: BTreeMap<String, String>,
+    #[serde(default)]
+    build: BuildSettings,
+    #[serde(default)]
+    test: ImageTests,
+}
+
+impl Metadata {
+    pub fn extends(&self) -> Option<&str> {
+        self.extends.as_deref()
+    }
+
+    pub fn version(&self) -> Option<&str> {
+        self.version.as_deref()
+    }
+
+    pub fn tags(&self) -> impl Iterator<Item = &str> {
+        self.tags.iter().map(String::as_str)
+    }
+
+    pub fn context(&self) -> Option<&str> {
+        self.context.as_deref()
+    }
+
+    pub fn contexts(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.contexts
+            .iter()
+            .map(|(name, path)| (name.as_str(), path.as_str()))
+    }
+
+    pub fn build_settings(&self) -> &BuildSettings {
+        &self.build
+    }
+
+    pub fn tests(&self) -> &ImageTests {
+        &self.test
+    }
+
+    pub fn ignore(&self) -> impl Iterator<Item = &str> {
+        self.ignore.iter().map(String::as_str)
+    }
+
+    pub fn inherit(&mut self, parent: Metadata) {
+        let own_ignore = mem::replace(&mut self.ignore, parent.ignore);
+
+        self.ignore.extend(own_ignore);
+    }
+}
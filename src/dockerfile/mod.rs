@@ -1,31 +1,45 @@
 mod add_file;
+mod build_settings;
+mod condition;
+mod conditional_items;
 mod copy_file;
+mod image_tests;
+mod metadata;
 mod packages;
 mod run_commands;
 mod single_or_multiple_items_visitor;
+mod source_location;
 mod stage;
 
-use self::stage::Stage;
+pub use self::metadata::Metadata;
+pub use self::source_location::SourceLocation;
+use self::{source_location::yaml_key_lines, stage::Stage};
 use failure::Fail;
 use serde::Deserialize;
 use serde_yaml::{Mapping, Number, Sequence, Value};
 use std::{
+    collections::HashMap,
     fmt::{self, Display, Formatter},
-    fs, io,
+    fs, io, iter, mem,
     num::ParseFloatError,
     path::Path,
 };
 use yaml_rust::{Yaml, YamlLoader};
 
+pub type Variables = HashMap<String, String>;
+
 #[derive(Debug, Deserialize)]
 #[serde(deny_unknown_fields)]
 pub struct Dockerfile {
+    #[serde(default)]
+    image: Metadata,
+    #[serde(default)]
     stages: Vec<Stage>,
 }
 
 #[derive(Debug, Fail)]
 pub enum FromFileError {
-    #[fail(display = "IO error reading YAML dockerfile: {}", _0)]
+    #[fail(display = "IO error reading dockerfile: {}", _0)]
     IoError(String, #[cause] io::Error),
 
     #[fail(display = "Failed to parse YAML dockerfile: {}", _0)]
@@ -33,6 +47,15 @@ pub enum FromFileError {
 
     #[fail(display = "Failed to deserialize YAML dockerfile: {}", _0)]
     DeserializationError(String, #[cause] serde_yaml::Error),
+
+    #[fail(display = "More than one image metadata document in: {}", _0)]
+    MultipleMetadataDocuments(String),
+
+    #[fail(display = "Failed to deserialize TOML dockerfile: {}", _0)]
+    TomlDeserializationError(String, #[cause] toml::de::Error),
+
+    #[fail(display = "Failed to deserialize JSON dockerfile: {}", _0)]
+    JsonDeserializationError(String, #[cause] serde_json::Error),
 }
 
 #[derive(Debug, Fail)]
@@ -50,6 +73,28 @@ pub enum ParseYamlError {
     BadYamlValue,
 }
 
+#[derive(Debug, Fail)]
+#[fail(
+    display = "Files are copied from stage {} which was disabled by its condition",
+    _0
+)]
+pub struct DisabledStageError(usize);
+
+#[derive(Debug, Fail)]
+pub enum MergeStagesError {
+    #[fail(
+        display = "Stage {} has no `from` and there is no previous stage to continue",
+        _0
+    )]
+    MissingFrom(usize),
+
+    #[fail(
+        display = "Stage {} continues the previous stage and can't have a `when` condition",
+        _0
+    )]
+    ConditionalContinuation(usize),
+}
+
 impl Dockerfile {
     pub fn from_file(file_path: impl AsRef<Path>) -> Result<Self, FromFileError> {
         let file_path = file_path.as_ref();
@@ -61,25 +106,190 @@ impl Dockerfile {
                 ParseYamlError::YamlRustError(error),
             )
         })?;
+        let key_lines = yaml_key_lines(&yaml_dockerfile).map_err(|error| {
+            FromFileError::ParseYamlError(
+                file_path.display().to_string(),
+                ParseYamlError::YamlRustError(error),
+            )
+        })?;
 
+        let mut image = None;
         let mut stages = Vec::with_capacity(dockerfile_stages.len());
 
-        for dockerfile_stage in dockerfile_stages {
-            let stage_value = convert_yaml_value(dockerfile_stage).map_err(|error| {
+        for (document, dockerfile_stage) in dockerfile_stages.into_iter().enumerate() {
+            let mut stage_value = convert_yaml_value(dockerfile_stage).map_err(|error| {
                 FromFileError::ParseYamlError(file_path.display().to_string(), error)
             })?;
-            let stage = serde_yaml::from_value(stage_value).map_err(|error| {
-                FromFileError::DeserializationError(file_path.display().to_string(), error)
-            })?;
 
-            stages.push(stage);
+            if let Some(metadata_value) = take_metadata_document(&mut stage_value) {
+                if image.is_some() {
+                    return Err(FromFileError::MultipleMetadataDocuments(
+                        file_path.display().to_string(),
+                    ));
+                }
+
+                image = Some(serde_yaml::from_value(metadata_value).map_err(|error| {
+                    FromFileError::DeserializationError(file_path.display().to_string(), error)
+                })?);
+            } else {
+                let mut stage: Stage = serde_yaml::from_value(stage_value).map_err(|error| {
+                    FromFileError::DeserializationError(file_path.display().to_string(), error)
+                })?;
+
+                if let Some(key_lines) = key_lines.get(document) {
+                    stage.set_key_lines(&file_path.display().to_string(), document + 1, key_lines);
+                }
+
+                stages.push(stage);
+            }
+        }
+
+        Ok(Dockerfile {
+            image: image.unwrap_or_default(),
+            stages,
+        })
+    }
+
+    pub fn from_toml_file(file_path: impl AsRef<Path>) -> Result<Self, FromFileError> {
+        let file_path = file_path.as_ref();
+        let toml_dockerfile = fs::read_to_string(file_path)
+            .map_err(|error| FromFileError::IoError(file_path.display().to_string(), error))?;
+
+        toml::from_str(&toml_dockerfile).map_err(|error| {
+            FromFileError::TomlDeserializationError(file_path.display().to_string(), error)
+        })
+    }
+
+    pub fn from_json_file(file_path: impl AsRef<Path>) -> Result<Self, FromFileError> {
+        let file_path = file_path.as_ref();
+        let json_dockerfile = fs::read_to_string(file_path)
+            .map_err(|error| FromFileError::IoError(file_path.display().to_string(), error))?;
+
+        serde_json::from_str(&json_dockerfile).map_err(|error| {
+            FromFileError::JsonDeserializationError(file_path.display().to_string(), error)
+        })
+    }
+
+    pub fn metadata(&self) -> &Metadata {
+        &self.image
+    }
+
+    pub fn set_source_file(&mut self, file: &str) {
+        for stage in &mut self.stages {
+            stage.set_source_file(file);
         }
+    }
 
-        Ok(Dockerfile { stages })
+    pub fn source_location(&self, line: usize) -> Option<&SourceLocation> {
+        self.stages
+            .iter()
+            .flat_map(|stage| stage.source_locations().chain(iter::once(None)))
+            .nth(line.checked_sub(1)?)
+            .flatten()
+    }
+
+    pub fn inherit(&mut self, parent: Dockerfile, parent_tag: &str) {
+        let mut own_stages = mem::replace(&mut self.stages, parent.stages);
+        let parent_stage_count = self.stages.len();
+
+        self.image.inherit(parent.image);
+
+        for stage in &mut self.stages {
+            stage.set_default_origin(parent_tag);
+        }
+
+        for stage in &mut own_stages {
+            stage.remap_stages(|index| index + parent_stage_count);
+        }
+
+        self.stages.extend(own_stages);
+    }
+
+    pub fn merge_continuation_stages(&mut self, image_tag: &str) -> Result<(), MergeStagesError> {
+        let merged_indices: Vec<usize> = self
+            .stages
+            .iter()
+            .scan(0usize, |stage_count, stage| {
+                if stage.from().is_some() {
+                    *stage_count += 1;
+                }
+
+                Some(stage_count.saturating_sub(1))
+            })
+            .collect();
+        let stages = mem::take(&mut self.stages);
+
+        for (index, stage) in stages.into_iter().enumerate() {
+            if stage.from().is_some() {
+                self.stages.push(stage);
+            } else if stage.has_condition() {
+                return Err(MergeStagesError::ConditionalContinuation(index));
+            } else {
+                match self.stages.last_mut() {
+                    Some(previous_stage) => previous_stage.extend(stage, image_tag),
+                    None => return Err(MergeStagesError::MissingFrom(index)),
+                }
+            }
+        }
+
+        for stage in &mut self.stages {
+            stage.remap_stages(|index| merged_indices.get(index).cloned().unwrap_or(index));
+        }
+
+        Ok(())
+    }
+
+    pub fn evaluate_conditions(&mut self, variables: &Variables) -> Result<(), DisabledStageError> {
+        let stage_indices: Vec<_> = self
+            .stages
+            .iter()
+            .scan(0, |next_index, stage| {
+                if stage.is_enabled(variables) {
+                    *next_index += 1;
+                    Some(Some(*next_index - 1))
+                } else {
+                    Some(None)
+                }
+            })
+            .collect();
+
+        let stages = mem::take(&mut self.stages);
+
+        for (mut stage, index) in stages.into_iter().zip(&stage_indices) {
+            if index.is_some() {
+                stage.evaluate_conditions(variables);
+                stage.renumber_stages(&stage_indices)?;
+
+                self.stages.push(stage);
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn context_sources(&self) -> impl Iterator<Item = &str> {
+        self.stages.iter().flat_map(Stage::context_sources)
+    }
+
+    pub fn named_context_sources(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.stages.iter().flat_map(Stage::named_context_sources)
     }
 
     pub fn from(&self) -> impl Iterator<Item = &str> {
-        self.stages.iter().map(Stage::from)
+        self.stages.iter().filter_map(Stage::from)
+    }
+
+    pub fn pin_base_images(&mut self, pinned_image: impl Fn(&str) -> Option<String>) {
+        for stage in &mut self.stages {
+            stage.pin_base_image(&pinned_image);
+        }
+    }
+
+    pub fn copy_sources(&self) -> impl Iterator<Item = &str> {
+        self.stages
+            .iter()
+            .flat_map(Stage::copy_contexts)
+            .filter(move |context| !self.image.contexts().any(|(name, _)| name == *context))
     }
 }
 
@@ -94,6 +304,15 @@ impl Display for Dockerfile {
     }
 }
 
+fn take_metadata_document(value: &mut Value) -> Option<Value> {
+    match value {
+        Value::Mapping(mapping) if mapping.len() == 1 => {
+            mapping.remove(&Value::String("image".to_owned()))
+        }
+        _ => None,
+    }
+}
+
 fn convert_yaml_value(value: Yaml) -> Result<Value, ParseYamlError> {
     match value {
         Yaml::Real(string) => {
@@ -125,3 +344,112 @@ fn convert_yaml_value(value: Yaml) -> Result<Value, ParseYamlError> {
         Yaml::BadValue => Err(ParseYamlError::BadYamlValue),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    const MULTI_STAGE_YAML: &str = "\
+from: alpine
+run:
+  - echo one
+  - echo two
+---
+from: debian
+install:
+  - curl
+  - when: ssl
+    packages: [openssl]
+run: echo three
+";
+
+    fn load(yaml: &str, variables: &[(&str, &str)]) -> Dockerfile {
+        let directory = TempDir::new().unwrap();
+        let file_path = directory.path().join("dockerfile.yml");
+
+        fs::write(&file_path, yaml).unwrap();
+
+        let mut dockerfile = Dockerfile::from_file(&file_path).unwrap();
+        let variables: Variables = variables
+            .iter()
+            .map(|&(name, value)| (name.to_owned(), value.to_owned()))
+            .collect();
+
+        dockerfile.set_source_file("ns/image/dockerfile.yml");
+        dockerfile.evaluate_conditions(&variables).unwrap();
+        dockerfile
+    }
+
+    fn source_line(dockerfile: &Dockerfile, line: usize) -> Option<usize> {
+        dockerfile.source_location(line).map(SourceLocation::line)
+    }
+
+    #[test]
+    fn maps_rendered_lines_back_to_yaml_lines() {
+        let dockerfile = load(MULTI_STAGE_YAML, &[("ssl", "1")]);
+        let rendered = dockerfile.to_string();
+        let lines: Vec<&str> = rendered.lines().collect();
+
+        assert_eq!(lines[1], "FROM alpine");
+        assert_eq!(source_line(&dockerfile, 2), Some(1));
+        assert_eq!(lines[4], "    && echo two");
+        assert_eq!(source_line(&dockerfile, 4), Some(3));
+        assert_eq!(source_line(&dockerfile, 5), Some(4));
+        assert_eq!(lines[5], "");
+        assert_eq!(source_line(&dockerfile, 6), None);
+        assert_eq!(lines[7], "FROM debian");
+        assert_eq!(source_line(&dockerfile, 8), Some(6));
+        assert_eq!(source_line(&dockerfile, 10), Some(7));
+        assert_eq!(lines[12].trim(), "curl \\");
+        assert_eq!(source_line(&dockerfile, 13), Some(8));
+        assert_eq!(lines[13].trim(), "openssl");
+        assert_eq!(source_line(&dockerfile, 14), Some(10));
+        assert_eq!(lines[15], "RUN echo three");
+        assert_eq!(source_line(&dockerfile, 16), Some(11));
+        assert_eq!(
+            dockerfile.source_location(16).unwrap().to_string(),
+            "ns/image/dockerfile.yml:11 (document 2, key `run`)"
+        );
+        assert_eq!(source_line(&dockerfile, lines.len() + 1), None);
+    }
+
+    #[test]
+    fn skips_lines_of_disabled_items() {
+        let dockerfile = load(MULTI_STAGE_YAML, &[]);
+        let rendered = dockerfile.to_string();
+        let lines: Vec<&str> = rendered.lines().collect();
+
+        assert_eq!(lines[12].trim(), "curl");
+        assert_eq!(source_line(&dockerfile, 13), Some(8));
+        assert_eq!(lines[14], "RUN echo three");
+        assert_eq!(source_line(&dockerfile, 15), Some(11));
+    }
+
+    #[test]
+    fn renders_env_in_a_stable_order() {
+        let yaml = "from: alpine\nenv:\n  D: \"4\"\n  B: \"2\"\n  C: \"3\"\n  A: \"1\"\n";
+        let first = load(yaml, &[]).to_string();
+
+        for _ in 0..5 {
+            assert_eq!(load(yaml, &[]).to_string(), first);
+        }
+
+        assert!(first.contains("ENV A=1 B=2 C=3 D=4\n"));
+    }
+
+    #[test]
+    fn alternate_rendering_omits_source_comments() {
+        let dockerfile = load(MULTI_STAGE_YAML, &[("ssl", "1")]);
+        let with_comments: Vec<String> = dockerfile
+            .to_string()
+            .lines()
+            .filter(|line| !line.starts_with("# from "))
+            .map(str::to_owned)
+            .collect();
+        let without_comments = format!("{:#}", dockerfile);
+
+        assert!(!without_comments.contains("# from"));
+        assert_eq!(without_comments.lines().collect::<Vec<_>>(), with_comments);
+    }
+}
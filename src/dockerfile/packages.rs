@@ -1,10 +1,48 @@
-use super::single_or_multiple_items_visitor::SingleOrMultipleItemsVisitor;
+use super::{conditional_items::ConditionalItems, source_location::SourceLocation, Variables};
 use serde::{Deserialize, Deserializer};
-use std::fmt::{self, Display, Formatter};
+use std::{
+    fmt::{self, Display, Formatter},
+    iter,
+};
 
 #[derive(Debug)]
 pub struct Packages {
-    packages: Vec<String>,
+    packages: ConditionalItems,
+}
+
+impl Packages {
+    pub fn evaluate_conditions(&mut self, variables: &Variables) {
+        self.packages.evaluate_conditions(variables);
+    }
+
+    pub fn extend(&mut self, other: Self) {
+        self.packages.extend(other.packages);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.packages.is_empty()
+    }
+
+    pub fn set_item_lines(&mut self, file: &str, document: usize, lines: &[Vec<usize>]) {
+        self.packages
+            .set_item_lines(file, document, "install", lines);
+    }
+
+    pub fn set_source_file(&mut self, file: &str) {
+        self.packages.set_source_file(file);
+    }
+
+    pub fn source_locations(&self) -> Vec<Option<&SourceLocation>> {
+        let command_lines = if self.packages.is_empty() { 2 } else { 3 };
+
+        iter::repeat_n(None, command_lines)
+            .chain(
+                self.packages
+                    .iter_with_locations()
+                    .map(|(_, location)| location),
+            )
+            .collect()
+    }
 }
 
 impl<'de> Deserialize<'de> for Packages {
@@ -13,7 +51,7 @@ impl<'de> Deserialize<'de> for Packages {
         D: Deserializer<'de>,
     {
         Ok(Packages {
-            packages: deserializer.deserialize_any(SingleOrMultipleItemsVisitor)?,
+            packages: ConditionalItems::deserialize(deserializer, "packages")?,
         })
     }
 }
@@ -22,19 +60,16 @@ impl Display for Packages {
     fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
         write!(
             formatter,
-            r#"RUN if [ "$(id -u)" -eq 0 ]; then apt-get update -y; else sudo apt-get update -y; fi"#
+            r#"RUN if [ "$(id -u)" -eq 0 ]; then SUDO=; else SUDO=sudo; fi \
+    && $SUDO apt-get update -y"#
         )?;
 
-        if self.packages.len() > 0 {
-            write!(
-                formatter,
-                r#" && if [ "$(id -u)" -eq 0 ]; then \
-                    apt-get install -y {packages}; \
-                else \
-                    sudo apt-get install -y {packages}; \
-                fi"#,
-                packages = self.packages.join(" "),
-            )?;
+        if !self.packages.is_empty() {
+            write!(formatter, " \\\n    && $SUDO apt-get install -y")?;
+
+            for package in self.packages.iter() {
+                write!(formatter, " \\\n        {}", package)?;
+            }
         }
 
         writeln!(formatter)
@@ -1,10 +1,44 @@
-use super::single_or_multiple_items_visitor::SingleOrMultipleItemsVisitor;
+use super::{conditional_items::ConditionalItems, source_location::SourceLocation, Variables};
 use serde::{Deserialize, Deserializer};
-use std::fmt::{self, Display, Formatter};
+use std::{
+    fmt::{self, Display, Formatter},
+    iter,
+};
 
 #[derive(Debug)]
 pub struct RunCommands {
-    commands: Vec<String>,
+    commands: ConditionalItems,
+}
+
+impl RunCommands {
+    pub fn evaluate_conditions(&mut self, variables: &Variables) {
+        self.commands.evaluate_conditions(variables);
+    }
+
+    pub fn extend(&mut self, other: Self) {
+        self.commands.extend(other.commands);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.commands.is_empty()
+    }
+
+    pub fn set_item_lines(&mut self, file: &str, document: usize, lines: &[Vec<usize>]) {
+        self.commands.set_item_lines(file, document, "run", lines);
+    }
+
+    pub fn set_source_file(&mut self, file: &str) {
+        self.commands.set_source_file(file);
+    }
+
+    pub fn source_locations(&self) -> Vec<Option<&SourceLocation>> {
+        self.commands
+            .iter_with_locations()
+            .flat_map(|(command, location)| {
+                iter::repeat_n(location, command.matches('\n').count() + 1)
+            })
+            .collect()
+    }
 }
 
 impl<'de> Deserialize<'de> for RunCommands {
@@ -13,7 +47,7 @@ impl<'de> Deserialize<'de> for RunCommands {
         D: Deserializer<'de>,
     {
         Ok(RunCommands {
-            commands: deserializer.deserialize_any(SingleOrMultipleItemsVisitor)?,
+            commands: ConditionalItems::deserialize(deserializer, "commands")?,
         })
     }
 }
@@ -26,7 +60,7 @@ impl Display for RunCommands {
             write!(formatter, "RUN {}", command)?;
 
             for command in commands {
-                write!(formatter, " && {}", command)?;
+                write!(formatter, " \\\n    && {}", command)?;
             }
         }
 
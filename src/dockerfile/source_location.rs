@@ -0,0 +1,247 @@
+use std::{
+    collections::HashMap,
+    fmt::{self, Display, Formatter},
+};
+use yaml_rust::{
+    parser::{Event, MarkedEventReceiver, Parser},
+    scanner::{Marker, ScanError},
+};
+
+#[derive(Clone, Debug)]
+pub struct SourceLocation {
+    file: String,
+    document: usize,
+    key: String,
+    line: usize,
+}
+
+impl SourceLocation {
+    pub fn new(
+        file: impl Into<String>,
+        document: usize,
+        key: impl Into<String>,
+        line: usize,
+    ) -> Self {
+        SourceLocation {
+            file: file.into(),
+            document,
+            key: key.into(),
+            line,
+        }
+    }
+
+    pub fn file(&self) -> &str {
+        &self.file
+    }
+
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    pub fn set_file(&mut self, file: impl Into<String>) {
+        self.file = file.into();
+    }
+}
+
+impl Display for SourceLocation {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        write!(
+            formatter,
+            "{}:{} (document {}, key `{}`)",
+            self.file, self.line, self.document, self.key
+        )
+    }
+}
+
+#[derive(Default)]
+pub struct KeyLines {
+    keys: HashMap<String, usize>,
+    items: HashMap<String, Vec<Vec<usize>>>,
+}
+
+impl KeyLines {
+    pub fn keys(&self) -> impl Iterator<Item = (&str, usize)> {
+        self.keys.iter().map(|(key, &line)| (key.as_str(), line))
+    }
+
+    pub fn item_lines(&self, key: &str) -> &[Vec<usize>] {
+        self.items.get(key).map(Vec::as_slice).unwrap_or(&[])
+    }
+}
+
+pub fn yaml_key_lines(yaml: &str) -> Result<Vec<KeyLines>, ScanError> {
+    let mut receiver = KeyLinesReceiver::default();
+
+    Parser::new(yaml.chars()).load(&mut receiver, true)?;
+
+    Ok(receiver.documents)
+}
+
+enum Node {
+    Mapping {
+        expecting_key: bool,
+        key: Option<String>,
+    },
+    Sequence,
+}
+
+#[derive(Default)]
+struct KeyLinesReceiver {
+    documents: Vec<KeyLines>,
+    nodes: Vec<Node>,
+}
+
+impl KeyLinesReceiver {
+    fn finish_node(&mut self) {
+        if let Some(Node::Mapping { expecting_key, .. }) = self.nodes.last_mut() {
+            *expecting_key = !*expecting_key;
+        }
+    }
+
+    fn top_level_key(&self) -> Option<&str> {
+        match self.nodes.first() {
+            Some(Node::Mapping {
+                expecting_key: false,
+                key: Some(key),
+            }) => Some(key),
+            _ => None,
+        }
+    }
+
+    fn start_item_entry(&mut self, line: Option<usize>) {
+        let key = match self.top_level_key() {
+            Some(key) => key.to_owned(),
+            None => return,
+        };
+
+        if let Some(document) = self.documents.last_mut() {
+            document
+                .items
+                .entry(key)
+                .or_default()
+                .push(line.into_iter().collect());
+        }
+    }
+
+    fn add_entry_item(&mut self, line: usize) {
+        let key = match self.top_level_key() {
+            Some(key) => key.to_owned(),
+            None => return,
+        };
+        let entry = self
+            .documents
+            .last_mut()
+            .and_then(|document| document.items.get_mut(&key))
+            .and_then(|entries| entries.last_mut());
+
+        if let Some(entry) = entry {
+            entry.push(line);
+        }
+    }
+
+    fn entry_node(&self) -> Option<&Node> {
+        match self.nodes.as_slice() {
+            [_, Node::Sequence, entry, ..] | [_, entry @ Node::Mapping { .. }, ..] => Some(entry),
+            _ => None,
+        }
+    }
+
+    fn is_entry_item(&self) -> bool {
+        let in_items = match self.entry_node() {
+            Some(Node::Mapping {
+                key: Some(key),
+                expecting_key: false,
+            }) => key != "when",
+            _ => false,
+        };
+
+        match self.nodes.as_slice() {
+            [_, Node::Sequence, _] | [_, Node::Mapping { .. }] => in_items,
+            [_, Node::Sequence, _, Node::Sequence] | [_, Node::Mapping { .. }, Node::Sequence] => {
+                in_items
+            }
+            _ => false,
+        }
+    }
+}
+
+impl MarkedEventReceiver for KeyLinesReceiver {
+    fn on_event(&mut self, event: Event, marker: Marker) {
+        match event {
+            Event::DocumentStart => {
+                self.documents.push(KeyLines::default());
+                self.nodes.clear();
+            }
+            Event::MappingStart(_) => {
+                if let [_] | [_, Node::Sequence] = self.nodes.as_slice() {
+                    self.start_item_entry(None);
+                }
+
+                self.nodes.push(Node::Mapping {
+                    expecting_key: true,
+                    key: None,
+                });
+            }
+            Event::SequenceStart(_) => self.nodes.push(Node::Sequence),
+            Event::MappingEnd | Event::SequenceEnd => {
+                self.nodes.pop();
+                self.finish_node();
+            }
+            Event::Scalar(value, ..) => {
+                match self.nodes.as_slice() {
+                    [Node::Mapping {
+                        expecting_key: true,
+                        ..
+                    }] => {
+                        if let Some(document) = self.documents.last_mut() {
+                            document
+                                .keys
+                                .entry(value.clone())
+                                .or_insert_with(|| marker.line());
+                        }
+                    }
+                    [_] | [_, Node::Sequence] => self.start_item_entry(Some(marker.line())),
+                    _ if self.is_entry_item() => self.add_entry_item(marker.line()),
+                    _ => {}
+                }
+
+                if let Some(Node::Mapping {
+                    expecting_key: true,
+                    key,
+                }) = self.nodes.last_mut()
+                {
+                    *key = Some(value);
+                }
+
+                self.finish_node();
+            }
+            Event::Alias(_) => self.finish_node(),
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn records_key_and_item_lines_per_document() {
+        let documents = yaml_key_lines(
+            "image:\n  version: 1\n---\nfrom: alpine\nrun:\n  - echo one\n  - when: debug\n    commands:\n      - echo two\n      - echo three\ninstall: curl\n",
+        )
+        .unwrap();
+
+        assert_eq!(documents.len(), 2);
+        assert_eq!(documents[0].keys().collect::<Vec<_>>(), vec![("image", 1)]);
+
+        let stage = &documents[1];
+        let mut keys: Vec<_> = stage.keys().collect();
+
+        keys.sort();
+        assert_eq!(keys, vec![("from", 4), ("install", 11), ("run", 5)]);
+        assert_eq!(stage.item_lines("run"), &[vec![6], vec![9, 10]][..]);
+        assert_eq!(stage.item_lines("install"), &[vec![11]][..]);
+        assert!(stage.item_lines("cmd").is_empty());
+    }
+}
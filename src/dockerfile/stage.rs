@@ -1,87 +1,357 @@
 use super::{
-    add_file::AddFile, copy_file::CopyFile, packages::Packages, run_commands::RunCommands,
+    add_file::AddFile,
+    condition::Condition,
+    copy_file::CopyFile,
+    packages::Packages,
+    run_commands::RunCommands,
+    source_location::{KeyLines, SourceLocation},
+    DisabledStageError, Variables,
 };
 use serde::Deserialize;
 use std::{
-    collections::HashMap,
+    collections::{BTreeMap, HashMap},
     fmt::{self, Display, Formatter},
 };
 
 #[derive(Debug, Deserialize)]
 #[serde(deny_unknown_fields)]
 pub struct Stage {
-    from: String,
+    from: Option<String>,
+    platform: Option<String>,
+    when: Option<Condition>,
     workdir: Option<String>,
     user: Option<String>,
     add: Option<Vec<AddFile>>,
     copy: Option<Vec<CopyFile>>,
-    env: Option<HashMap<String, String>>,
+    env: Option<BTreeMap<String, String>>,
     install: Option<Packages>,
     run: Option<RunCommands>,
     entrypoint: Option<String>,
     cmd: Option<String>,
+    #[serde(skip)]
+    origin: Option<String>,
+    #[serde(skip)]
+    extended_by: Vec<String>,
+    #[serde(skip)]
+    locations: HashMap<String, SourceLocation>,
 }
 
 impl Stage {
-    pub fn from(&self) -> &str {
-        &self.from
+    pub fn from(&self) -> Option<&str> {
+        self.from.as_deref()
     }
-}
 
-impl Display for Stage {
-    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
-        writeln!(formatter, "FROM {}", self.from)?;
+    pub fn context_sources(&self) -> impl Iterator<Item = &str> {
+        let added_files = self.add.iter().flatten().map(AddFile::source);
+        let copied_files = self
+            .copy
+            .iter()
+            .flatten()
+            .filter(|copy_file| copy_file.stage().is_none() && copy_file.context().is_none())
+            .map(CopyFile::source);
+
+        added_files
+            .chain(copied_files)
+            .flat_map(str::split_whitespace)
+    }
+
+    pub fn pin_base_image(&mut self, pinned_image: impl Fn(&str) -> Option<String>) {
+        if let Some(pinned) = self.from.as_deref().and_then(&pinned_image) {
+            self.from = Some(pinned);
+        }
+
+        for copy_file in self.copy.iter_mut().flatten() {
+            copy_file.pin_context(&pinned_image);
+        }
+    }
+
+    pub fn copy_contexts(&self) -> impl Iterator<Item = &str> {
+        self.copy
+            .iter()
+            .flatten()
+            .filter(|copy_file| copy_file.stage().is_none())
+            .filter_map(CopyFile::context)
+    }
+
+    pub fn named_context_sources(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.copy
+            .iter()
+            .flatten()
+            .filter(|copy_file| copy_file.stage().is_none())
+            .filter_map(|copy_file| {
+                let context = copy_file.context()?;
+
+                Some(
+                    copy_file
+                        .source()
+                        .split_whitespace()
+                        .map(move |source| (context, source)),
+                )
+            })
+            .flatten()
+    }
+
+    pub fn has_condition(&self) -> bool {
+        self.when.is_some()
+    }
+
+    pub fn set_default_origin(&mut self, image_tag: &str) {
+        if self.origin.is_none() {
+            self.origin = Some(image_tag.to_owned());
+        }
+    }
+
+    pub fn set_key_lines(&mut self, file: &str, document: usize, key_lines: &KeyLines) {
+        self.locations = key_lines
+            .keys()
+            .map(|(key, line)| {
+                (
+                    key.to_owned(),
+                    SourceLocation::new(file, document, key, line),
+                )
+            })
+            .collect();
+
+        if let Some(packages) = &mut self.install {
+            packages.set_item_lines(file, document, key_lines.item_lines("install"));
+        }
+
+        if let Some(run_commands) = &mut self.run {
+            run_commands.set_item_lines(file, document, key_lines.item_lines("run"));
+        }
+    }
+
+    pub fn set_source_file(&mut self, file: &str) {
+        for location in self.locations.values_mut() {
+            location.set_file(file);
+        }
+
+        if let Some(packages) = &mut self.install {
+            packages.set_source_file(file);
+        }
+
+        if let Some(run_commands) = &mut self.run {
+            run_commands.set_source_file(file);
+        }
+    }
+
+    pub fn extend(&mut self, mut other: Stage, image_tag: &str) {
+        for key in &["platform", "workdir", "user", "entrypoint", "cmd"] {
+            if let Some(location) = other.locations.remove(*key) {
+                self.locations.insert(key.to_string(), location);
+            }
+        }
+
+        for (key, location) in other.locations {
+            self.locations.entry(key).or_insert(location);
+        }
+
+        replace_if_some(&mut self.platform, other.platform);
+        replace_if_some(&mut self.workdir, other.workdir);
+        replace_if_some(&mut self.user, other.user);
+        replace_if_some(&mut self.entrypoint, other.entrypoint);
+        replace_if_some(&mut self.cmd, other.cmd);
+
+        match (&mut self.add, other.add) {
+            (Some(add), Some(other_add)) => add.extend(other_add),
+            (add, other_add) => replace_if_some(add, other_add),
+        }
+
+        match (&mut self.copy, other.copy) {
+            (Some(copy), Some(other_copy)) => copy.extend(other_copy),
+            (copy, other_copy) => replace_if_some(copy, other_copy),
+        }
+
+        match (&mut self.env, other.env) {
+            (Some(env), Some(other_env)) => env.extend(other_env),
+            (env, other_env) => replace_if_some(env, other_env),
+        }
+
+        match (&mut self.install, other.install) {
+            (Some(packages), Some(other_packages)) => packages.extend(other_packages),
+            (packages, other_packages) => replace_if_some(packages, other_packages),
+        }
+
+        match (&mut self.run, other.run) {
+            (Some(run_commands), Some(other_run_commands)) => {
+                run_commands.extend(other_run_commands)
+            }
+            (run_commands, other_run_commands) => replace_if_some(run_commands, other_run_commands),
+        }
+
+        if self.origin.is_some() {
+            self.extended_by.push(image_tag.to_owned());
+        }
+    }
+
+    pub fn is_enabled(&self, variables: &Variables) -> bool {
+        match &self.when {
+            Some(condition) => condition.evaluate(variables),
+            None => true,
+        }
+    }
+
+    pub fn evaluate_conditions(&mut self, variables: &Variables) {
+        self.when = None;
+
+        if let Some(packages) = &mut self.install {
+            let was_empty = packages.is_empty();
+
+            packages.evaluate_conditions(variables);
+
+            if packages.is_empty() && !was_empty {
+                self.install = None;
+            }
+        }
+
+        if let Some(run_commands) = &mut self.run {
+            run_commands.evaluate_conditions(variables);
+
+            if run_commands.is_empty() {
+                self.run = None;
+            }
+        }
+    }
+
+    pub fn renumber_stages(
+        &mut self,
+        stage_indices: &[Option<usize>],
+    ) -> Result<(), DisabledStageError> {
+        if let Some(copy) = &mut self.copy {
+            for copy_file in copy {
+                if let Some(stage) = copy_file.stage() {
+                    match stage_indices.get(stage) {
+                        Some(Some(new_stage)) => copy_file.set_stage(*new_stage),
+                        Some(None) => return Err(DisabledStageError(stage)),
+                        None => {}
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn remap_stages(&mut self, stage_index: impl Fn(usize) -> usize) {
+        for copy_file in self.copy.iter_mut().flatten() {
+            if let Some(stage) = copy_file.stage() {
+                copy_file.set_stage(stage_index(stage));
+            }
+        }
+    }
+
+    pub fn source_locations(&self) -> impl Iterator<Item = Option<&SourceLocation>> {
+        let origin_line = self.origin.as_ref().map(|_| None);
+        let instruction_lines =
+            self.instructions()
+                .into_iter()
+                .flat_map(move |(key, instruction)| {
+                    let location = self.locations.get(key);
+                    let comment_line = location.map(|_| None);
+                    let item_locations = match key {
+                        "install" => self.install.as_ref().map(Packages::source_locations),
+                        "run" => self.run.as_ref().map(RunCommands::source_locations),
+                        _ => None,
+                    }
+                    .unwrap_or_default();
+                    let lines = (0..instruction.lines().count())
+                        .map(move |line| item_locations.get(line).cloned().flatten().or(location));
+
+                    comment_line.into_iter().chain(lines)
+                });
+
+        origin_line.into_iter().chain(instruction_lines)
+    }
+
+    fn instructions(&self) -> Vec<(&'static str, String)> {
+        let mut instructions = Vec::new();
+
+        if let Some(from) = &self.from {
+            let platform = match &self.platform {
+                Some(platform) => format!("--platform={} ", platform),
+                None => String::new(),
+            };
+
+            instructions.push(("from", format!("FROM {}{}\n", platform, from)));
+        }
 
         if let Some(workdir) = &self.workdir {
-            writeln!(formatter, "WORKDIR {}", workdir)?;
+            instructions.push(("workdir", format!("WORKDIR {}\n", workdir)));
         }
 
         if let Some(user) = &self.user {
-            writeln!(formatter, "USER {}", user)?;
+            instructions.push(("user", format!("USER {}\n", user)));
         }
 
-        if let Some(add) = &self.add {
-            add.iter()
-                .map(|add_file| add_file.fmt(formatter))
-                .find(Result::is_err)
-                .unwrap_or(Ok(()))?;
+        if let Some(add) = self.add.as_ref().filter(|add| !add.is_empty()) {
+            instructions.push(("add", add.iter().map(ToString::to_string).collect()));
         }
 
-        if let Some(copy) = &self.copy {
-            copy.iter()
-                .map(|copy_file| copy_file.fmt(formatter))
-                .find(Result::is_err)
-                .unwrap_or(Ok(()))?;
+        if let Some(copy) = self.copy.as_ref().filter(|copy| !copy.is_empty()) {
+            instructions.push(("copy", copy.iter().map(ToString::to_string).collect()));
         }
 
         if let Some(env) = &self.env {
-            if env.len() > 0 {
-                write!(formatter, "ENV")?;
+            let mut instruction = String::new();
+
+            if !env.is_empty() {
+                instruction.push_str("ENV");
 
                 for (key, value) in env {
-                    write!(formatter, " {}={}", key, value)?;
+                    instruction.push_str(&format!(" {}={}", key, value));
                 }
             }
 
-            writeln!(formatter)?;
+            instruction.push('\n');
+            instructions.push(("env", instruction));
         }
 
         if let Some(packages) = &self.install {
-            packages.fmt(formatter)?;
+            instructions.push(("install", packages.to_string()));
         }
 
         if let Some(run_commands) = &self.run {
-            run_commands.fmt(formatter)?;
+            instructions.push(("run", run_commands.to_string()));
         }
 
         if let Some(entrypoint) = &self.entrypoint {
-            writeln!(formatter, "ENTRYPOINT {}", entrypoint)?;
+            instructions.push(("entrypoint", format!("ENTRYPOINT {}\n", entrypoint)));
         }
 
         if let Some(command) = &self.cmd {
-            writeln!(formatter, "CMD {}", command)?;
+            instructions.push(("cmd", format!("CMD {}\n", command)));
+        }
+
+        instructions
+    }
+}
+
+impl Display for Stage {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        if let Some(origin) = &self.origin {
+            write!(formatter, "# inherited from {}", origin)?;
+
+            if !self.extended_by.is_empty() {
+                write!(formatter, ", extended by {}", self.extended_by.join(", "))?;
+            }
+
+            writeln!(formatter)?;
+        }
+
+        for (key, instruction) in self.instructions() {
+            if let Some(location) = self.locations.get(key).filter(|_| !formatter.alternate()) {
+                writeln!(formatter, "# from {}:{}", location.file(), location.line())?;
+            }
+
+            write!(formatter, "{}", instruction)?;
         }
 
         Ok(())
     }
 }
+
+fn replace_if_some<T>(target: &mut Option<T>, value: Option<T>) {
+    if value.is_some() {
+        *target = value;
+    }
+}
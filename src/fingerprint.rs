@@ -0,0 +1,77 @@
+use sha2::{Digest, Sha256};
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+pub struct Fingerprint {
+    hasher: Sha256,
+}
+
+impl Fingerprint {
+    pub fn new() -> Self {
+        Fingerprint {
+            hasher: Sha256::new(),
+        }
+    }
+
+    pub fn add(&mut self, name: &str, value: impl AsRef<[u8]>) -> &mut Self {
+        let value = value.as_ref();
+
+        self.hasher.input(name.as_bytes());
+        self.hasher.input((value.len() as u64).to_le_bytes());
+        self.hasher.input(value);
+        self
+    }
+
+    pub fn add_context_files(
+        &mut self,
+        context: &Path,
+        source: &str,
+    ) -> Result<&mut Self, io::Error> {
+        if source.contains("://") {
+            return Ok(self.add("url", source));
+        }
+
+        let pattern = context.join(source.trim_start_matches('/'));
+        let mut paths: Vec<PathBuf> = match glob::glob(&pattern.to_string_lossy()) {
+            Ok(paths) => paths.filter_map(Result::ok).collect(),
+            Err(_) => vec![pattern],
+        };
+
+        paths.sort();
+
+        for path in paths {
+            self.add_path(context, &path)?;
+        }
+
+        Ok(self)
+    }
+
+    fn add_path(&mut self, context: &Path, path: &Path) -> Result<(), io::Error> {
+        let relative_path = path.strip_prefix(context).unwrap_or(path);
+
+        if path.is_dir() {
+            let mut entries = fs::read_dir(path)?
+                .map(|entry| entry.map(|entry| entry.path()))
+                .collect::<Result<Vec<_>, _>>()?;
+
+            entries.sort();
+
+            self.add("directory", relative_path.to_string_lossy().as_bytes());
+
+            for entry in entries {
+                self.add_path(context, &entry)?;
+            }
+        } else if path.exists() {
+            self.add("file", relative_path.to_string_lossy().as_bytes());
+            self.add("contents", fs::read(path)?);
+        }
+
+        Ok(())
+    }
+
+    pub fn finish(self) -> String {
+        format!("{:x}", self.hasher.result())
+    }
+}
@@ -0,0 +1,394 @@
+mod plain_dockerfile;
+
+use self::plain_dockerfile::PlainDockerfile;
+use super::{
+    dockerfile::{self, DisabledStageError, Dockerfile, Metadata, SourceLocation, Variables},
+    image_reference::ImageReference,
+};
+use failure::Fail;
+use std::{
+    fmt::{self, Display, Formatter},
+    fs, io, iter,
+    path::{Path, PathBuf},
+};
+
+const DEFINITION_FILES: &[&str] = &["dockerfile.yml", "dockerfile.toml", "dockerfile.json"];
+
+#[derive(Debug)]
+pub enum ImageDefinition {
+    Stages(Box<Dockerfile>),
+    Plain(PlainDockerfile),
+}
+
+#[derive(Debug, Fail)]
+pub enum LoadImageDefinitionError {
+    #[fail(
+        display = "No dockerfile.yml, .toml, .json or Dockerfile found in: {}",
+        _0
+    )]
+    NotFound(String),
+
+    #[fail(display = "Multiple image definition files ({}) found in: {}", _1, _0)]
+    MultipleDefinitions(String, String),
+
+    #[fail(display = "Failed to load dockerfile")]
+    StagesError(#[cause] dockerfile::FromFileError),
+
+    #[fail(display = "Failed to read Dockerfile: {}", _0)]
+    PlainError(String, #[cause] io::Error),
+
+    #[fail(display = "Image extends itself through: {}", _0)]
+    ExtendsCycle(String),
+
+    #[fail(
+        display = "Image {} can't extend {} because it's defined with a plain Dockerfile",
+        _0, _1
+    )]
+    ExtendsPlainDockerfile(String, String),
+
+    #[fail(
+        display = "Image {} can't extend {} because it copies {} from the build context of {}",
+        _0, _1, _2, _1
+    )]
+    ExtendsLocalFiles(String, String, String),
+
+    #[fail(display = "Failed to merge stages of image: {}", _0)]
+    MergeStagesError(String, #[cause] dockerfile::MergeStagesError),
+}
+
+impl ImageDefinition {
+    pub fn load(
+        images_dir: impl AsRef<Path>,
+        image_tag: &str,
+    ) -> Result<Self, LoadImageDefinitionError> {
+        Self::load_extending(images_dir.as_ref(), image_tag, &mut Vec::new())
+    }
+
+    fn load_extending(
+        images_dir: &Path,
+        image_tag: &str,
+        chain: &mut Vec<String>,
+    ) -> Result<Self, LoadImageDefinitionError> {
+        if chain.iter().any(|tag| tag == image_tag) {
+            chain.push(image_tag.to_owned());
+
+            return Err(LoadImageDefinitionError::ExtendsCycle(chain.join(" -> ")));
+        }
+
+        let mut definition = Self::load_file(images_dir, image_tag)?;
+
+        if let ImageDefinition::Stages(dockerfile) = &mut definition {
+            let parent_tag = dockerfile
+                .metadata()
+                .extends()
+                .map(|parent_tag| qualify_image_tag(parent_tag, image_tag));
+
+            if let Some(parent_tag) = parent_tag {
+                chain.push(image_tag.to_owned());
+
+                let parent = Self::load_extending(images_dir, &parent_tag, chain)?;
+
+                chain.pop();
+
+                match parent {
+                    ImageDefinition::Stages(parent_dockerfile) => {
+                        let local_source = parent_dockerfile
+                            .context_sources()
+                            .find(|source| !source.contains("://"))
+                            .map(str::to_owned);
+
+                        if let Some(source) = local_source {
+                            return Err(LoadImageDefinitionError::ExtendsLocalFiles(
+                                image_tag.to_owned(),
+                                parent_tag,
+                                source,
+                            ));
+                        }
+
+                        dockerfile.inherit(*parent_dockerfile, &parent_tag)
+                    }
+                    ImageDefinition::Plain(_) => {
+                        return Err(LoadImageDefinitionError::ExtendsPlainDockerfile(
+                            image_tag.to_owned(),
+                            parent_tag,
+                        ));
+                    }
+                }
+            }
+
+            dockerfile
+                .merge_continuation_stages(image_tag)
+                .map_err(|error| {
+                    LoadImageDefinitionError::MergeStagesError(image_tag.to_owned(), error)
+                })?;
+        }
+
+        Ok(definition)
+    }
+
+    fn load_file(images_dir: &Path, image_tag: &str) -> Result<Self, LoadImageDefinitionError> {
+        let directory = image_directory(images_dir, image_tag);
+        let directory = directory.as_path();
+        let plain_path = directory.join("Dockerfile");
+        let definition_files: Vec<_> = DEFINITION_FILES
+            .iter()
+            .filter(|file_name| directory.join(file_name).exists())
+            .collect();
+
+        if definition_files.len() > 1 || (!definition_files.is_empty() && plain_path.exists()) {
+            let mut file_names: Vec<&str> = definition_files.into_iter().cloned().collect();
+
+            if plain_path.exists() {
+                file_names.push("Dockerfile");
+            }
+
+            Err(LoadImageDefinitionError::MultipleDefinitions(
+                directory.display().to_string(),
+                file_names.join(", "),
+            ))
+        } else if let Some(&&file_name) = definition_files.first() {
+            let file_path = directory.join(file_name);
+            let dockerfile = match file_name {
+                "dockerfile.toml" => Dockerfile::from_toml_file(file_path),
+                "dockerfile.json" => Dockerfile::from_json_file(file_path),
+                _ => Dockerfile::from_file(file_path),
+            };
+
+            dockerfile
+                .map(|mut dockerfile| {
+                    dockerfile.set_source_file(&format!("{}/{}", image_tag, file_name));
+                    ImageDefinition::Stages(Box::new(dockerfile))
+                })
+                .map_err(LoadImageDefinitionError::StagesError)
+        } else if plain_path.exists() {
+            PlainDockerfile::from_file(&plain_path)
+                .map(ImageDefinition::Plain)
+                .map_err(|error| {
+                    LoadImageDefinitionError::PlainError(plain_path.display().to_string(), error)
+                })
+        } else {
+            Err(LoadImageDefinitionError::NotFound(
+                directory.display().to_string(),
+            ))
+        }
+    }
+
+    pub fn metadata(&self) -> Option<&Metadata> {
+        match self {
+            ImageDefinition::Stages(dockerfile) => Some(dockerfile.metadata()),
+            ImageDefinition::Plain(_) => None,
+        }
+    }
+
+    pub fn source_location(&self, line: usize) -> Option<&SourceLocation> {
+        match self {
+            ImageDefinition::Stages(dockerfile) => dockerfile.source_location(line),
+            ImageDefinition::Plain(_) => None,
+        }
+    }
+
+    pub fn dockerignore(
+        &self,
+        context: impl AsRef<Path>,
+        image_directory: impl AsRef<Path>,
+    ) -> Result<Option<String>, io::Error> {
+        let dockerfile = match self {
+            ImageDefinition::Stages(dockerfile) => dockerfile,
+            ImageDefinition::Plain(_) => return Ok(None),
+        };
+        let existing_dockerignore = context.as_ref().join(".dockerignore");
+        let mut contents = if existing_dockerignore.is_file() {
+            fs::read_to_string(existing_dockerignore)?
+        } else {
+            String::new()
+        };
+
+        if !contents.is_empty() && !contents.ends_with('\n') {
+            contents.push('\n');
+        }
+
+        let context = fs::canonicalize(context)?;
+        let image_directory = fs::canonicalize(image_directory)?;
+
+        if let Ok(relative_directory) = image_directory.strip_prefix(&context) {
+            for file_name in DEFINITION_FILES {
+                let pattern = relative_directory.join(file_name);
+                let components: Vec<_> = pattern
+                    .components()
+                    .map(|component| component.as_os_str().to_string_lossy())
+                    .collect();
+
+                contents.push_str(&components.join("/"));
+                contents.push('\n');
+            }
+        }
+
+        for pattern in dockerfile.metadata().ignore() {
+            contents.push_str(pattern);
+            contents.push('\n');
+        }
+
+        Ok(Some(contents))
+    }
+
+    pub fn pin_base_images(&mut self, pinned_image: impl Fn(&str) -> Option<String>) {
+        match self {
+            ImageDefinition::Stages(dockerfile) => dockerfile.pin_base_images(pinned_image),
+            ImageDefinition::Plain(dockerfile) => dockerfile.pin_base_images(pinned_image),
+        }
+    }
+
+    pub fn evaluate_conditions(&mut self, variables: &Variables) -> Result<(), DisabledStageError> {
+        match self {
+            ImageDefinition::Stages(dockerfile) => dockerfile.evaluate_conditions(variables),
+            ImageDefinition::Plain(_) => Ok(()),
+        }
+    }
+
+    pub fn context_sources(&self) -> Box<dyn Iterator<Item = &str> + '_> {
+        match self {
+            ImageDefinition::Stages(dockerfile) => Box::new(dockerfile.context_sources()),
+            ImageDefinition::Plain(dockerfile) => Box::new(dockerfile.context_sources()),
+        }
+    }
+
+    pub fn named_context_sources(&self) -> Box<dyn Iterator<Item = (&str, &str)> + '_> {
+        match self {
+            ImageDefinition::Stages(dockerfile) => Box::new(dockerfile.named_context_sources()),
+            ImageDefinition::Plain(_) => Box::new(iter::empty()),
+        }
+    }
+
+    pub fn from(&self) -> Box<dyn Iterator<Item = &str> + '_> {
+        match self {
+            ImageDefinition::Stages(dockerfile) => Box::new(dockerfile.from()),
+            ImageDefinition::Plain(dockerfile) => Box::new(dockerfile.from()),
+        }
+    }
+
+    pub fn copy_sources(&self) -> Box<dyn Iterator<Item = &str> + '_> {
+        match self {
+            ImageDefinition::Stages(dockerfile) => Box::new(dockerfile.copy_sources()),
+            ImageDefinition::Plain(dockerfile) => Box::new(dockerfile.copy_sources()),
+        }
+    }
+}
+
+impl Display for ImageDefinition {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        match self {
+            ImageDefinition::Stages(dockerfile) => dockerfile.fmt(formatter),
+            ImageDefinition::Plain(dockerfile) => dockerfile.fmt(formatter),
+        }
+    }
+}
+
+pub fn list_image_tags(images_dir: impl AsRef<Path>) -> Result<Vec<String>, io::Error> {
+    let mut image_tags = Vec::new();
+
+    for namespace_entry in fs::read_dir(images_dir)? {
+        let namespace_path = namespace_entry?.path();
+
+        if !namespace_path.is_dir() {
+            continue;
+        }
+
+        for image_entry in fs::read_dir(&namespace_path)? {
+            let image_path = image_entry?.path();
+
+            if has_definition(&image_path) {
+                if let (Some(namespace), Some(name)) =
+                    (namespace_path.file_name(), image_path.file_name())
+                {
+                    image_tags.push(format!(
+                        "{}/{}",
+                        namespace.to_string_lossy(),
+                        name.to_string_lossy()
+                    ));
+                }
+            }
+        }
+    }
+
+    image_tags.sort();
+
+    Ok(image_tags)
+}
+
+fn has_definition(directory: &Path) -> bool {
+    directory.is_dir()
+        && DEFINITION_FILES
+            .iter()
+            .chain(&["Dockerfile"])
+            .any(|file_name| directory.join(file_name).is_file())
+}
+
+fn image_directory(images_dir: &Path, image_tag: &str) -> PathBuf {
+    match image_tag.find('/') {
+        Some(position) => images_dir
+            .join(&image_tag[..position])
+            .join(&image_tag[(position + 1)..]),
+        None => images_dir.join(image_tag),
+    }
+}
+
+fn qualify_image_tag(image_tag: &str, relative_to: &str) -> String {
+    let image_tag = match image_tag.parse::<ImageReference>() {
+        Ok(reference) => reference.without_version().to_string(),
+        Err(_) => image_tag.to_owned(),
+    };
+
+    match (image_tag.find('/'), relative_to.find('/')) {
+        (None, Some(position)) => format!("{}/{}", &relative_to[..position], image_tag),
+        _ => image_tag,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{super::test_support::write_image, *};
+    use tempfile::TempDir;
+
+    #[test]
+    fn extending_image_copies_from_its_own_stage() {
+        let images_dir = TempDir::new().unwrap();
+
+        write_image(
+            &images_dir,
+            "ns/parent",
+            "from: alpine\nrun: echo parent\n---\nfrom: debian\n",
+        );
+        write_image(
+            &images_dir,
+            "ns/child",
+            "image:\n  extends: parent\n---\nrun: echo continued\n---\nfrom: golang\nrun: go build\n---\nfrom: debian\ncopy:\n  - from: /app\n    to: /app\n    stage: 1\n",
+        );
+
+        let definition = ImageDefinition::load(images_dir.path(), "ns/child").unwrap();
+        let dockerfile = definition.to_string();
+
+        assert!(dockerfile.contains("COPY --from=2 /app /app\n"));
+        assert_eq!(dockerfile.matches("FROM ").count(), 4);
+    }
+
+    #[test]
+    fn extending_image_that_copies_local_files_fails() {
+        let images_dir = TempDir::new().unwrap();
+
+        write_image(
+            &images_dir,
+            "ns/parent",
+            "from: alpine\ncopy:\n  - from: app.py\n    to: /app/\n",
+        );
+        write_image(&images_dir, "ns/child", "image:\n  extends: parent\n");
+
+        match ImageDefinition::load(images_dir.path(), "ns/child") {
+            Err(LoadImageDefinitionError::ExtendsLocalFiles(image_tag, parent_tag, source)) => {
+                assert_eq!(image_tag, "ns/child");
+                assert_eq!(parent_tag, "ns/parent");
+                assert_eq!(source, "app.py");
+            }
+            _ => panic!("expected ExtendsLocalFiles"),
+        }
+    }
+}
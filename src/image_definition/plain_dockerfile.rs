@@ -0,0 +1,357 @@
+use std::{
+    cmp::Reverse,
+    collections::{HashMap, VecDeque},
+    fmt::{self, Display, Formatter},
+    fs, io,
+    path::Path,
+};
+
+#[derive(Debug)]
+pub struct PlainDockerfile {
+    contents: String,
+    from: Vec<String>,
+    copy_sources: Vec<String>,
+    context_sources: Vec<String>,
+    global_arguments: HashMap<String, String>,
+}
+
+impl PlainDockerfile {
+    pub fn from_file(file_path: impl AsRef<Path>) -> Result<Self, io::Error> {
+        let contents = fs::read_to_string(file_path)?;
+        let (from, copy_sources, global_arguments) = Self::parse_source_images(&contents);
+        let context_sources = Self::parse_context_sources(&contents);
+
+        Ok(PlainDockerfile {
+            contents,
+            from,
+            copy_sources,
+            context_sources,
+            global_arguments,
+        })
+    }
+
+    fn parse_source_images(contents: &str) -> (Vec<String>, Vec<String>, HashMap<String, String>) {
+        let mut from = Vec::new();
+        let mut copy_sources = Vec::new();
+        let mut stage_names = Vec::new();
+        let mut global_arguments = HashMap::new();
+
+        for instruction in Self::instructions(contents) {
+            let mut words = instruction.split_whitespace();
+            let keyword = match words.next() {
+                Some(keyword) => keyword.to_ascii_uppercase(),
+                None => continue,
+            };
+
+            if keyword == "ARG" && stage_names.is_empty() && from.is_empty() {
+                for argument in words {
+                    let mut parts = argument.splitn(2, '=');
+
+                    if let Some(name) = parts.next() {
+                        let value = parts.next().unwrap_or("").trim_matches('"');
+
+                        global_arguments.insert(name.to_owned(), value.to_owned());
+                    }
+                }
+            } else if keyword == "FROM" {
+                let mut words = words.skip_while(|word| word.starts_with("--"));
+
+                if let Some(image) = words.next() {
+                    let image = Self::substitute_arguments(image, &global_arguments);
+
+                    if !stage_names.contains(&image.to_ascii_lowercase()) {
+                        from.push(image);
+                    }
+                }
+
+                if let (Some(keyword), Some(name)) = (words.next(), words.next()) {
+                    if keyword.eq_ignore_ascii_case("AS") {
+                        stage_names.push(name.to_ascii_lowercase());
+                    }
+                }
+            } else if keyword == "COPY" {
+                let sources = words
+                    .take_while(|word| word.starts_with("--"))
+                    .filter_map(|flag| flag.strip_prefix("--from="))
+                    .map(|source| source.trim_matches('"'));
+
+                for source in sources {
+                    let is_stage = source.parse::<usize>().is_ok()
+                        || stage_names.contains(&source.to_ascii_lowercase());
+
+                    if !is_stage && !copy_sources.iter().any(|copied| copied == source) {
+                        copy_sources.push(source.to_owned());
+                    }
+                }
+            }
+        }
+
+        (from, copy_sources, global_arguments)
+    }
+
+    fn parse_context_sources(contents: &str) -> Vec<String> {
+        let mut context_sources = Vec::new();
+
+        for instruction in Self::instructions(contents) {
+            let mut words = instruction.split_whitespace();
+
+            match words.next() {
+                Some(keyword)
+                    if keyword.eq_ignore_ascii_case("ADD")
+                        || keyword.eq_ignore_ascii_case("COPY") => {}
+                _ => continue,
+            }
+
+            let (flags, arguments): (Vec<_>, Vec<_>) =
+                words.partition(|word| word.starts_with("--"));
+
+            if flags.iter().any(|flag| flag.starts_with("--from=")) {
+                continue;
+            }
+
+            let arguments: Vec<_> = arguments
+                .iter()
+                .map(|argument| argument.trim_matches(|c| c == '[' || c == ']' || c == ','))
+                .map(|argument| argument.trim_matches('"'))
+                .filter(|argument| !argument.is_empty() && !argument.starts_with("<<"))
+                .collect();
+
+            if let Some((_destination, sources)) = arguments.split_last() {
+                context_sources.extend(sources.iter().map(|source| source.to_string()));
+            }
+        }
+
+        context_sources
+    }
+
+    fn instructions(contents: &str) -> Vec<String> {
+        let mut instructions = Vec::new();
+        let mut current = String::new();
+        let mut heredocs = VecDeque::new();
+
+        for line in contents.lines() {
+            let line = line.trim();
+
+            if let Some(delimiter) = heredocs.front() {
+                if line == delimiter {
+                    heredocs.pop_front();
+                }
+
+                continue;
+            }
+
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            if let Some(continued) = line.strip_suffix('\\') {
+                current.push_str(continued);
+                current.push(' ');
+            } else {
+                current.push_str(line);
+                heredocs.extend(Self::heredoc_delimiters(&current));
+                instructions.push(current);
+                current = String::new();
+            }
+        }
+
+        if !current.is_empty() {
+            instructions.push(current);
+        }
+
+        instructions
+    }
+
+    fn heredoc_delimiters(instruction: &str) -> impl Iterator<Item = String> + '_ {
+        instruction
+            .split_whitespace()
+            .filter_map(|word| word.strip_prefix("<<"))
+            .map(|word| {
+                word.trim_start_matches('-')
+                    .trim_start_matches(['"', '\''])
+                    .chars()
+                    .take_while(|c| c.is_alphanumeric() || *c == '_')
+                    .collect::<String>()
+            })
+            .filter(|delimiter| !delimiter.is_empty())
+    }
+
+    fn substitute_arguments(image: &str, arguments: &HashMap<String, String>) -> String {
+        let mut result = image.to_owned();
+        let mut arguments: Vec<_> = arguments.iter().collect();
+
+        arguments.sort_by_key(|(name, _)| Reverse(name.len()));
+
+        for (name, value) in arguments {
+            result = result
+                .replace(&format!("${{{}}}", name), value)
+                .replace(&format!("${}", name), value);
+        }
+
+        result
+    }
+
+    pub fn context_sources(&self) -> impl Iterator<Item = &str> {
+        self.context_sources.iter().map(String::as_str)
+    }
+
+    pub fn from(&self) -> impl Iterator<Item = &str> {
+        self.from.iter().map(String::as_str)
+    }
+
+    pub fn pin_base_images(&mut self, pinned_image: impl Fn(&str) -> Option<String>) {
+        let mut contents = String::with_capacity(self.contents.len());
+        let mut instruction = String::new();
+        let mut heredocs = VecDeque::new();
+
+        for line in self.contents.split_inclusive('\n') {
+            let content = line.trim();
+
+            if let Some(delimiter) = heredocs.front() {
+                if content == delimiter {
+                    heredocs.pop_front();
+                }
+
+                contents.push_str(line);
+                continue;
+            }
+
+            match self.pin_line(line, &pinned_image) {
+                Some(pinned_line) => contents.push_str(&pinned_line),
+                None => contents.push_str(line),
+            }
+
+            if content.is_empty() || content.starts_with('#') {
+                continue;
+            }
+
+            instruction.push_str(content);
+            instruction.push(' ');
+
+            if !content.ends_with('\\') {
+                heredocs.extend(Self::heredoc_delimiters(&instruction));
+                instruction.clear();
+            }
+        }
+
+        for image in self.from.iter_mut().chain(&mut self.copy_sources) {
+            if let Some(pinned) = pinned_image(image) {
+                *image = pinned;
+            }
+        }
+
+        self.contents = contents;
+    }
+
+    fn pin_line(
+        &self,
+        line: &str,
+        pinned_image: impl Fn(&str) -> Option<String>,
+    ) -> Option<String> {
+        let content = line.trim_start();
+        let indentation = &line[..(line.len() - content.len())];
+        let line_ending = &content[content.trim_end().len()..];
+        let mut words: Vec<String> = content.split_whitespace().map(str::to_owned).collect();
+        let keyword = words.first()?.to_ascii_uppercase();
+
+        if keyword == "FROM" {
+            let image_position = words
+                .iter()
+                .skip(1)
+                .position(|word| !word.starts_with("--"))?
+                + 1;
+            let image = Self::substitute_arguments(&words[image_position], &self.global_arguments);
+
+            words[image_position] = pinned_image(&image)?;
+        } else if keyword == "COPY" {
+            let mut pinned_any = false;
+
+            for word in words.iter_mut().skip(1) {
+                if !word.starts_with("--") {
+                    break;
+                }
+
+                let pinned = word
+                    .strip_prefix("--from=")
+                    .map(|source| source.trim_matches('"'))
+                    .and_then(&pinned_image);
+
+                if let Some(pinned) = pinned {
+                    *word = format!("--from={}", pinned);
+                    pinned_any = true;
+                }
+            }
+
+            if !pinned_any {
+                return None;
+            }
+        } else {
+            return None;
+        }
+
+        Some(format!("{}{}{}", indentation, words.join(" "), line_ending))
+    }
+
+    pub fn copy_sources(&self) -> impl Iterator<Item = &str> {
+        self.copy_sources.iter().map(String::as_str)
+    }
+}
+
+impl Display for PlainDockerfile {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        write!(formatter, "{}", self.contents)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HEREDOC_DOCKERFILE: &str = "\
+FROM alpine AS build
+RUN <<EOF
+FROM evil
+COPY --from=evil /a /b
+EOF
+COPY <<-\"CONF\" /etc/app.conf
+  FROM inside
+  CONF
+FROM debian
+COPY --from=build /out /out
+COPY app.py /app/
+";
+
+    #[test]
+    fn skips_heredoc_bodies() {
+        let (from, copy_sources, _) = PlainDockerfile::parse_source_images(HEREDOC_DOCKERFILE);
+
+        assert_eq!(from, vec!["alpine", "debian"]);
+        assert!(copy_sources.is_empty());
+        assert_eq!(
+            PlainDockerfile::parse_context_sources(HEREDOC_DOCKERFILE),
+            vec!["app.py"]
+        );
+    }
+
+    #[test]
+    fn pinning_leaves_heredoc_bodies_alone() {
+        let (from, copy_sources, global_arguments) =
+            PlainDockerfile::parse_source_images(HEREDOC_DOCKERFILE);
+        let mut dockerfile = PlainDockerfile {
+            contents: HEREDOC_DOCKERFILE.to_owned(),
+            from,
+            copy_sources,
+            context_sources: Vec::new(),
+            global_arguments,
+        };
+
+        dockerfile.pin_base_images(|image| Some(format!("{}@sha256:0", image)));
+
+        assert!(dockerfile
+            .contents
+            .contains("FROM alpine@sha256:0 AS build\n"));
+        assert!(dockerfile.contents.contains("\nFROM evil\n"));
+        assert!(dockerfile.contents.contains("\n  FROM inside\n"));
+        assert!(dockerfile.contents.contains("FROM debian@sha256:0\n"));
+    }
+}
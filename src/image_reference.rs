@@ -0,0 +1,242 @@
+use failure::Fail;
+use std::{
+    fmt::{self, Display, Formatter},
+    str::FromStr,
+};
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ImageReference {
+    registry: Option<String>,
+    namespace: Option<String>,
+    name: String,
+    tag: Option<String>,
+    digest: Option<String>,
+}
+
+#[derive(Debug, Fail)]
+pub enum ParseImageReferenceError {
+    #[fail(display = "Image reference has no name: {}", _0)]
+    MissingName(String),
+
+    #[fail(display = "Image reference has an empty tag: {}", _0)]
+    EmptyTag(String),
+
+    #[fail(display = "Image reference has an empty digest: {}", _0)]
+    EmptyDigest(String),
+}
+
+impl ImageReference {
+    pub fn registry(&self) -> Option<&str> {
+        self.registry.as_deref()
+    }
+
+    pub fn namespace(&self) -> Option<&str> {
+        self.namespace.as_deref()
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn tag(&self) -> Option<&str> {
+        self.tag.as_deref()
+    }
+
+    pub fn set_namespace(&mut self, namespace: impl Into<String>) {
+        self.namespace = Some(namespace.into());
+    }
+
+    pub fn repository(&self) -> String {
+        match &self.namespace {
+            Some(namespace) => format!("{}/{}", namespace, self.name),
+            None => self.name.clone(),
+        }
+    }
+
+    pub fn with_tag(&self, tag: impl Into<String>) -> Self {
+        ImageReference {
+            tag: Some(tag.into()),
+            digest: None,
+            ..self.clone()
+        }
+    }
+
+    pub fn without_version(&self) -> Self {
+        ImageReference {
+            tag: None,
+            digest: None,
+            ..self.clone()
+        }
+    }
+}
+
+impl FromStr for ImageReference {
+    type Err = ParseImageReferenceError;
+
+    fn from_str(reference: &str) -> Result<Self, Self::Err> {
+        let (remainder, digest) = match reference.find('@') {
+            Some(position) => (&reference[..position], Some(&reference[(position + 1)..])),
+            None => (reference, None),
+        };
+
+        if digest == Some("") {
+            return Err(ParseImageReferenceError::EmptyDigest(reference.to_owned()));
+        }
+
+        let (remainder, tag) = match remainder.rfind(':') {
+            Some(position) if !remainder[position..].contains('/') => {
+                (&remainder[..position], Some(&remainder[(position + 1)..]))
+            }
+            _ => (remainder, None),
+        };
+
+        if tag == Some("") {
+            return Err(ParseImageReferenceError::EmptyTag(reference.to_owned()));
+        }
+
+        let mut components: Vec<&str> = remainder.split('/').collect();
+        let registry = if components.len() > 1 && is_registry(components[0]) {
+            Some(components.remove(0))
+        } else {
+            None
+        };
+        let name = components.pop().unwrap_or("");
+
+        if name.is_empty() || components.iter().any(|component| component.is_empty()) {
+            return Err(ParseImageReferenceError::MissingName(reference.to_owned()));
+        }
+
+        Ok(ImageReference {
+            registry: registry.map(str::to_owned),
+            namespace: if components.is_empty() {
+                None
+            } else {
+                Some(components.join("/"))
+            },
+            name: name.to_owned(),
+            tag: tag.map(str::to_owned),
+            digest: digest.map(str::to_owned),
+        })
+    }
+}
+
+fn is_registry(component: &str) -> bool {
+    component.contains('.') || component.contains(':') || component == "localhost"
+}
+
+impl Display for ImageReference {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        if let Some(registry) = &self.registry {
+            write!(formatter, "{}/", registry)?;
+        }
+
+        write!(formatter, "{}", self.repository())?;
+
+        if let Some(tag) = &self.tag {
+            write!(formatter, ":{}", tag)?;
+        }
+
+        if let Some(digest) = &self.digest {
+            write!(formatter, "@{}", digest)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reference(
+        registry: Option<&str>,
+        namespace: Option<&str>,
+        name: &str,
+        tag: Option<&str>,
+        digest: Option<&str>,
+    ) -> ImageReference {
+        ImageReference {
+            registry: registry.map(str::to_owned),
+            namespace: namespace.map(str::to_owned),
+            name: name.to_owned(),
+            tag: tag.map(str::to_owned),
+            digest: digest.map(str::to_owned),
+        }
+    }
+
+    #[test]
+    fn parses_registry_with_port() {
+        let parsed: ImageReference = "localhost:5000/ns/app".parse().unwrap();
+
+        assert_eq!(
+            parsed,
+            reference(Some("localhost:5000"), Some("ns"), "app", None, None)
+        );
+        assert_eq!(parsed.to_string(), "localhost:5000/ns/app");
+
+        let parsed: ImageReference = "registry.example.com:5000/ns/app:1.0".parse().unwrap();
+
+        assert_eq!(
+            parsed,
+            reference(
+                Some("registry.example.com:5000"),
+                Some("ns"),
+                "app",
+                Some("1.0"),
+                None
+            )
+        );
+    }
+
+    #[test]
+    fn parses_tag_and_digest() {
+        let parsed: ImageReference = "ns/app:1.0@sha256:abc".parse().unwrap();
+
+        assert_eq!(
+            parsed,
+            reference(None, Some("ns"), "app", Some("1.0"), Some("sha256:abc"))
+        );
+        assert_eq!(parsed.to_string(), "ns/app:1.0@sha256:abc");
+        assert_eq!(parsed.without_version().to_string(), "ns/app");
+        assert_eq!(parsed.with_tag("2.0").to_string(), "ns/app:2.0");
+    }
+
+    #[test]
+    fn parses_reference_without_namespace() {
+        let parsed: ImageReference = "alpine:3.8".parse().unwrap();
+
+        assert_eq!(parsed, reference(None, None, "alpine", Some("3.8"), None));
+        assert_eq!(parsed.repository(), "alpine");
+
+        let parsed: ImageReference = "localhost/app".parse().unwrap();
+
+        assert_eq!(
+            parsed,
+            reference(Some("localhost"), None, "app", None, None)
+        );
+    }
+
+    #[test]
+    fn rejects_empty_components() {
+        assert!(matches!(
+            "ns/app:".parse::<ImageReference>(),
+            Err(ParseImageReferenceError::EmptyTag(_))
+        ));
+        assert!(matches!(
+            "ns/app:1.0@".parse::<ImageReference>(),
+            Err(ParseImageReferenceError::EmptyDigest(_))
+        ));
+        assert!(matches!(
+            "".parse::<ImageReference>(),
+            Err(ParseImageReferenceError::MissingName(_))
+        ));
+        assert!(matches!(
+            "ns//app".parse::<ImageReference>(),
+            Err(ParseImageReferenceError::MissingName(_))
+        ));
+        assert!(matches!(
+            "localhost:5000/".parse::<ImageReference>(),
+            Err(ParseImageReferenceError::MissingName(_))
+        ));
+    }
+}
@@ -0,0 +1,90 @@
+use failure::Fail;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+const LOCKFILE_NAME: &str = "dkr.lock";
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Lockfile {
+    #[serde(default)]
+    images: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Fail)]
+pub enum LockfileError {
+    #[fail(display = "Failed to read lockfile: {}", _0)]
+    Read(String, #[cause] io::Error),
+
+    #[fail(display = "Failed to parse lockfile: {}", _0)]
+    Parse(String, #[cause] serde_yaml::Error),
+
+    #[fail(display = "Failed to serialize lockfile")]
+    Serialize(#[cause] serde_yaml::Error),
+
+    #[fail(display = "Failed to write lockfile: {}", _0)]
+    Write(String, #[cause] io::Error),
+}
+
+impl Lockfile {
+    pub fn load(images_dir: impl AsRef<Path>) -> Result<Self, LockfileError> {
+        let path = Self::path(images_dir);
+
+        if !path.exists() {
+            return Ok(Lockfile::default());
+        }
+
+        let contents = fs::read_to_string(&path)
+            .map_err(|error| LockfileError::Read(path.display().to_string(), error))?;
+
+        serde_yaml::from_str(&contents)
+            .map_err(|error| LockfileError::Parse(path.display().to_string(), error))
+    }
+
+    pub fn save(&self, images_dir: impl AsRef<Path>) -> Result<(), LockfileError> {
+        let path = Self::path(images_dir);
+        let mut contents = serde_yaml::to_string(self).map_err(LockfileError::Serialize)?;
+
+        contents.push('\n');
+
+        fs::write(&path, contents)
+            .map_err(|error| LockfileError::Write(path.display().to_string(), error))
+    }
+
+    fn path(images_dir: impl AsRef<Path>) -> PathBuf {
+        images_dir.as_ref().join(LOCKFILE_NAME)
+    }
+
+    pub fn contains(&self, image: &str) -> bool {
+        self.images.contains_key(image)
+    }
+
+    pub fn insert(&mut self, image: impl Into<String>, digest: impl Into<String>) {
+        self.images.insert(image.into(), digest.into());
+    }
+
+    pub fn retain(&mut self, images: &[&str]) -> Vec<String> {
+        let unused: Vec<String> = self
+            .images
+            .keys()
+            .filter(|image| !images.contains(&image.as_str()))
+            .cloned()
+            .collect();
+
+        for image in &unused {
+            self.images.remove(image);
+        }
+
+        unused
+    }
+
+    pub fn pinned_image(&self, image: &str) -> Option<String> {
+        self.images
+            .get(image)
+            .map(|digest| format!("{}@{}", image, digest))
+    }
+}
@@ -1,15 +1,25 @@
 mod arguments;
+mod build_graph;
+mod build_log;
+mod build_scheduler;
+mod build_summary;
 mod commands;
 mod config;
 mod docker;
 mod docker_environment;
 mod docker_image;
 mod dockerfile;
+mod fingerprint;
+mod image_definition;
+mod image_reference;
+mod lockfile;
+#[cfg(test)]
+mod test_support;
 
-use std::io;
 use self::{arguments::Arguments, commands::RunCommandError, config::Config};
 use app_dirs::AppInfo;
 use failure::Fail;
+use std::{io, process};
 use structopt::StructOpt;
 
 const APP_INFO: AppInfo = AppInfo {
@@ -30,6 +40,8 @@ fn main() {
         for cause in dyn_error.iter_causes() {
             eprintln!("       {}", cause);
         }
+
+        process::exit(1);
     }
 }
 
@@ -49,7 +61,9 @@ fn run() -> Result<(), RunError> {
     if arguments.is_config_volume_enabled() && config_volume_exists() {
         run_in_container(arguments).map_err(RunError::RunContainerError)
     } else {
-        arguments.run_command(config).map_err(RunError::RunCommandError)
+        arguments
+            .run_command(config)
+            .map_err(RunError::RunCommandError)
     }
 }
 
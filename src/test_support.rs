@@ -0,0 +1,9 @@
+use std::fs;
+use tempfile::TempDir;
+
+pub fn write_image(images_dir: &TempDir, image_tag: &str, dockerfile: &str) {
+    let image_dir = images_dir.path().join(image_tag);
+
+    fs::create_dir_all(&image_dir).unwrap();
+    fs::write(image_dir.join("dockerfile.yml"), dockerfile).unwrap();
+}